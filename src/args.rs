@@ -1,4 +1,6 @@
 use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 
 use clap::{App, Arg, SubCommand};
 
@@ -11,6 +13,18 @@ pub const TARGETS_ARG: &'static str = "targets";
 pub const TEST_ARG: &'static str = "test";
 pub const RELEASE_ARG: &'static str = "release";
 pub const NUPKG_DIR_ARG: &'static str = "nupkg-dir";
+pub const STRIP_ARG: &'static str = "strip";
+pub const NO_STRIP_ARG: &'static str = "no-strip";
+pub const VERSION_FILE_ARG: &'static str = "version-file";
+pub const PACK_ONLY_TARGETS_ARG: &'static str = "pack-only-targets";
+pub const CONFIG_ARG: &'static str = "config";
+pub const ALLOW_PRERELEASE_DEPS_ARG: &'static str = "allow-prerelease-deps";
+pub const IGNORE_MISSING_TARGETS_ARG: &'static str = "ignore-missing-targets";
+pub const FAIL_ON_WARNINGS_ARG: &'static str = "fail-on-warnings";
+pub const LIB_MANIFEST_ARG: &'static str = "libs";
+pub const QUIET_ARG: &'static str = "quiet";
+pub const BUILD_WIDTH_ARG: &'static str = "build-width";
+pub const COMPRESSION_ARG: &'static str = "compression";
 
 pub fn target_path_arg(target: CrossTarget) -> String {
     format!("{}-path", target.rid())
@@ -26,7 +40,8 @@ lazy_static! {
     static ref TARGET_PATHS: Vec<PartialArg> = {
         let archs = vec![
             Arch::x86,
-            Arch::x64
+            Arch::x64,
+            Arch::arm64
         ];
 
         archs.into_iter().flat_map(|arch| {
@@ -34,6 +49,7 @@ lazy_static! {
                 CrossTarget::Windows(arch),
                 CrossTarget::MacOS(arch),
                 CrossTarget::Linux(arch),
+                CrossTarget::FreeBSD(arch),
             ]
         })
         .map(|target| PartialArg {
@@ -46,7 +62,7 @@ lazy_static! {
 }
 
 pub fn app<'a, 'b>() -> App<'a, 'b> {
-    let local_args = vec![
+    let mut local_args = vec![
         Arg::with_name(CARGO_WORK_DIR_ARG)
             .long(CARGO_WORK_DIR_ARG)
             .takes_value(true)
@@ -65,8 +81,44 @@ pub fn app<'a, 'b>() -> App<'a, 'b> {
             .help("run an optimised build"),
         Arg::with_name(NUPKG_DIR_ARG)
             .long(NUPKG_DIR_ARG)
+            .alias("output-directory")
             .takes_value(true)
-            .help("path to save the nupkg"),
+            .help("path to save the nupkg, created if it doesn't exist"),
+        Arg::with_name(STRIP_ARG)
+            .long(STRIP_ARG)
+            .help("strip debug sections from libs before packing"),
+        Arg::with_name(NO_STRIP_ARG)
+            .long(NO_STRIP_ARG)
+            .conflicts_with(STRIP_ARG)
+            .help("don't strip debug sections from libs, even if the pack config does"),
+        Arg::with_name(COMPRESSION_ARG)
+            .long(COMPRESSION_ARG)
+            .takes_value(true)
+            .possible_values(&["deflated", "bzip2"])
+            .help("the method to compress package entries with, where 'bzip2' requires the 'bzip2' feature"),
+        Arg::with_name(VERSION_FILE_ARG)
+            .long(VERSION_FILE_ARG)
+            .takes_value(true)
+            .help("path to a file containing the package version"),
+        Arg::with_name(CONFIG_ARG)
+            .long(CONFIG_ARG)
+            .takes_value(true)
+            .help("path to a nuget-rs.toml file with default pack options"),
+        Arg::with_name(ALLOW_PRERELEASE_DEPS_ARG)
+            .long(ALLOW_PRERELEASE_DEPS_ARG)
+            .help("allow dependencies on prerelease package versions"),
+        Arg::with_name(FAIL_ON_WARNINGS_ARG)
+            .long(FAIL_ON_WARNINGS_ARG)
+            .help("fail the build if any warnings are found"),
+        Arg::with_name(BUILD_WIDTH_ARG)
+            .long(BUILD_WIDTH_ARG)
+            .takes_value(true)
+            .validator(|width| width.parse::<usize>().map(|_| ()).map_err(|e| e.to_string()))
+            .help("zero-pad the dev build number to at least this many digits, like 'dev.00042'"),
+        Arg::with_name(LIB_MANIFEST_ARG)
+            .long(LIB_MANIFEST_ARG)
+            .takes_value(true)
+            .help("path to a file of 'rid=path' lines for prebuilt libs, like '@libs.txt'"),
     ];
 
     let path_args = TARGET_PATHS.iter().map(|arg| {
@@ -101,10 +153,73 @@ pub fn app<'a, 'b>() -> App<'a, 'b> {
             .help("run an optimised build"),
         Arg::with_name(NUPKG_DIR_ARG)
             .long(NUPKG_DIR_ARG)
+            .alias("output-directory")
+            .takes_value(true)
+            .help("path to save the nupkg, created if it doesn't exist"),
+        Arg::with_name(STRIP_ARG)
+            .long(STRIP_ARG)
+            .help("strip debug sections from libs before packing"),
+        Arg::with_name(NO_STRIP_ARG)
+            .long(NO_STRIP_ARG)
+            .conflicts_with(STRIP_ARG)
+            .help("don't strip debug sections from libs, even if the pack config does"),
+        Arg::with_name(COMPRESSION_ARG)
+            .long(COMPRESSION_ARG)
             .takes_value(true)
-            .help("path to save the nupkg"),
+            .possible_values(&["deflated", "bzip2"])
+            .help("the method to compress package entries with, where 'bzip2' requires the 'bzip2' feature"),
+        Arg::with_name(VERSION_FILE_ARG)
+            .long(VERSION_FILE_ARG)
+            .takes_value(true)
+            .help("path to a file containing the package version"),
+        Arg::with_name(CONFIG_ARG)
+            .long(CONFIG_ARG)
+            .takes_value(true)
+            .help("path to a nuget-rs.toml file with default pack options"),
+        Arg::with_name(ALLOW_PRERELEASE_DEPS_ARG)
+            .long(ALLOW_PRERELEASE_DEPS_ARG)
+            .help("allow dependencies on prerelease package versions"),
+        Arg::with_name(FAIL_ON_WARNINGS_ARG)
+            .long(FAIL_ON_WARNINGS_ARG)
+            .help("fail the build if any warnings are found"),
     ];
 
+    let pack_only_targets_arg = Arg::with_name(PACK_ONLY_TARGETS_ARG)
+        .long(PACK_ONLY_TARGETS_ARG)
+        .takes_value(true)
+        .multiple(true)
+        .validator(|rid| match Target::from_rid(&rid).is_unknown() {
+            true => Err(format!("'{}' isn't a known dotnet rid", rid)),
+            false => Ok(()),
+        })
+        .help("subset of the built dotnet rids to include in the nupkg");
+
+    local_args.push(pack_only_targets_arg.clone());
+    cross_args.push(pack_only_targets_arg);
+
+    // Quiet builds only print a JSON build report, so without one there's no output
+    if cfg!(feature = "json") {
+        let quiet_arg = Arg::with_name(QUIET_ARG)
+            .long(QUIET_ARG)
+            .help("only log errors and print a JSON build report");
+
+        local_args.push(quiet_arg.clone());
+        cross_args.push(quiet_arg);
+    }
+
+    cross_args.push(
+        Arg::with_name(IGNORE_MISSING_TARGETS_ARG)
+            .long(IGNORE_MISSING_TARGETS_ARG)
+            .help("skip targets whose lib wasn't found instead of failing"),
+    );
+
+    cross_args.push(
+        Arg::with_name(LIB_MANIFEST_ARG)
+            .long(LIB_MANIFEST_ARG)
+            .takes_value(true)
+            .help("path to a file of 'rid=path' lines for prebuilt libs, like '@libs.txt'"),
+    );
+
     cross_args.extend(path_args);
 
     App::new("cargo-nuget")
@@ -133,11 +248,30 @@ pub enum Profile {
     Release,
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+/// The platform a lib is built for.
+///
+/// Targets are compared and hashed by their rid, so different variants
+/// that produce the same rid, like `Local` and its `Cross` equivalent, are equal.
+#[derive(Clone, Copy, Debug)]
 pub enum Target {
     Local,
     Unknown,
     Cross(CrossTarget),
+    Versioned(CrossTarget, OsVersion),
+}
+
+impl PartialEq for Target {
+    fn eq(&self, other: &Self) -> bool {
+        self.rid() == other.rid()
+    }
+}
+
+impl Eq for Target {}
+
+impl Hash for Target {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rid().hash(state)
+    }
 }
 
 impl Target {
@@ -146,6 +280,8 @@ impl Target {
             Target::Local => CrossTarget::local(),
             Target::Unknown => None,
             Target::Cross(target) => Some(target),
+            Target::Versioned(target, version) if target.supports_version(version) => Some(target),
+            Target::Versioned(_, _) => None,
         }
     }
 
@@ -153,19 +289,92 @@ impl Target {
         self.cross().is_none()
     }
 
+    /// Pin this target to a specific OS version, like `win10-x64`.
+    ///
+    /// Unknown targets stay unknown, and so do versions the platform's rids can't express,
+    /// like a Windows version with a minor part.
+    pub fn with_version(self, version: OsVersion) -> Self {
+        match self.cross() {
+            Some(target) if target.supports_version(version) => Target::Versioned(target, version),
+            _ => Target::Unknown,
+        }
+    }
+
     pub fn rid(&self) -> Cow<'static, str> {
+        match *self {
+            Target::Versioned(target, version) => target.versioned_rid(version).unwrap_or_else(|| "any".into()),
+            _ => match self.cross() {
+                Some(target) => target.rid(),
+                _ => "any".into(),
+            },
+        }
+    }
+
+    /// Whether this target is a Windows platform.
+    pub fn is_windows(&self) -> bool {
         match self.cross() {
-            Some(target) => target.rid(),
-            _ => "any".into(),
+            Some(CrossTarget::Windows(_)) => true,
+            _ => false,
         }
     }
 
+    /// Whether this target is a Unix platform.
+    ///
+    /// Like `cfg(unix)`, this includes MacOS and FreeBSD.
+    pub fn is_unix(&self) -> bool {
+        match self.cross() {
+            Some(CrossTarget::Linux(_)) | Some(CrossTarget::MacOS(_)) | Some(CrossTarget::FreeBSD(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this target is a MacOS platform.
+    pub fn is_macos(&self) -> bool {
+        match self.cross() {
+            Some(CrossTarget::MacOS(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Get the platform specific extension for libraries built for this target.
+    pub fn extension(&self) -> Option<&'static str> {
+        self.cross().map(|target| target.extension())
+    }
+
     pub fn from_rid(rid: &str) -> Self {
-        match CrossTarget::from_rid(rid) {
+        match CrossTarget::parse_rid(rid) {
+            Some((target, Some(version))) => Target::Versioned(target, version),
+            Some((target, None)) => Target::Cross(target),
+            None => Target::Unknown,
+        }
+    }
+
+    /// Get the target for a Rust target triple, like `i686-pc-windows-msvc`.
+    pub fn from_triple(triple: &str) -> Self {
+        match CrossTarget::from_triple(triple) {
             Some(target) => Target::Cross(target),
             None => Target::Unknown,
         }
     }
+
+    /// Get the rids NuGet's runtime graph falls back to for this target, most specific first.
+    ///
+    /// The target's own rid isn't included, except for versioned targets,
+    /// which fall back to their unversioned rid, like `win10-x64` to `win-x64`.
+    pub fn rid_graph_fallbacks(&self) -> Vec<&'static str> {
+        match *self {
+            Target::Versioned(target, _) => {
+                let mut fallbacks = vec![target.static_rid()];
+                fallbacks.extend(target.rid_graph_fallbacks());
+
+                fallbacks
+            }
+            _ => match self.cross() {
+                Some(target) => target.rid_graph_fallbacks(),
+                None => vec!["base"],
+            },
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
@@ -173,6 +382,7 @@ pub enum CrossTarget {
     Windows(Arch),
     Linux(Arch),
     MacOS(Arch),
+    FreeBSD(Arch),
 }
 
 impl CrossTarget {
@@ -180,36 +390,226 @@ impl CrossTarget {
         local_target()
     }
 
+    pub fn arch(&self) -> Arch {
+        match *self {
+            CrossTarget::Windows(arch) => arch,
+            CrossTarget::MacOS(arch) => arch,
+            CrossTarget::Linux(arch) => arch,
+            CrossTarget::FreeBSD(arch) => arch,
+        }
+    }
+
+    pub fn platform(&self) -> &'static str {
+        match *self {
+            CrossTarget::Windows(_) => "win",
+            CrossTarget::MacOS(_) => "osx",
+            CrossTarget::Linux(_) => "linux",
+            CrossTarget::FreeBSD(_) => "freebsd",
+        }
+    }
+
     pub fn rid(&self) -> Cow<'static, str> {
+        rid(self.platform(), self.arch().rid())
+    }
+
+    /// Get the rids NuGet's runtime graph falls back to for this target, most specific first.
+    ///
+    /// Unix platforms fall back through `unix-{arch}` before `unix`.
+    pub fn rid_graph_fallbacks(&self) -> Vec<&'static str> {
+        let unix = match self.arch() {
+            Arch::x64 => "unix-x64",
+            Arch::x86 => "unix-x86",
+            Arch::arm64 => "unix-arm64",
+        };
+
+        match *self {
+            CrossTarget::Windows(_) => vec!["win", "any", "base"],
+            CrossTarget::MacOS(_) => vec!["osx", unix, "unix", "any", "base"],
+            CrossTarget::Linux(_) => vec!["linux", unix, "unix", "any", "base"],
+            CrossTarget::FreeBSD(_) => vec!["freebsd", unix, "unix", "any", "base"],
+        }
+    }
+
+    /// Get the unversioned rid as a static string.
+    fn static_rid(&self) -> &'static str {
+        match (*self, self.arch()) {
+            (CrossTarget::Windows(_), Arch::x64) => "win-x64",
+            (CrossTarget::Windows(_), Arch::x86) => "win-x86",
+            (CrossTarget::Windows(_), Arch::arm64) => "win-arm64",
+            (CrossTarget::MacOS(_), Arch::x64) => "osx-x64",
+            (CrossTarget::MacOS(_), Arch::x86) => "osx-x86",
+            (CrossTarget::MacOS(_), Arch::arm64) => "osx-arm64",
+            (CrossTarget::Linux(_), Arch::x64) => "linux-x64",
+            (CrossTarget::Linux(_), Arch::x86) => "linux-x86",
+            (CrossTarget::Linux(_), Arch::arm64) => "linux-arm64",
+            (CrossTarget::FreeBSD(_), Arch::x64) => "freebsd-x64",
+            (CrossTarget::FreeBSD(_), Arch::x86) => "freebsd-x86",
+            (CrossTarget::FreeBSD(_), Arch::arm64) => "freebsd-arm64",
+        }
+    }
+
+    /// Get the rid for a specific version of the platform.
+    ///
+    /// Windows versions are appended directly, like `win10-x64`.
+    /// Other platforms separate the version with dots, like `osx.11.0-arm64`.
+    /// If the platform's rids can't express the version then there's no rid.
+    pub fn versioned_rid(&self, version: OsVersion) -> Option<Cow<'static, str>> {
+        if !self.supports_version(version) {
+            return None;
+        }
+
+        let platform = match *self {
+            CrossTarget::Windows(_) => format!("{}{}", self.platform(), version.major),
+            _ => format!("{}.{}", self.platform(), version),
+        };
+
+        Some(format!("{}-{}", platform, self.arch().rid()).into())
+    }
+
+    /// Whether the platform's rids can express a version.
+    ///
+    /// Windows versions are the literal digits in the rid, like `81` in `win81`,
+    /// so they can't have a minor part.
+    pub fn supports_version(&self, version: OsVersion) -> bool {
         match *self {
-            CrossTarget::Windows(arch) => rid("win", arch.rid()),
-            CrossTarget::MacOS(arch) => rid("osx", arch.rid()),
-            CrossTarget::Linux(arch) => rid("linux", arch.rid()),
+            CrossTarget::Windows(_) => version.minor.is_none(),
+            _ => true,
         }
     }
 
+    /// Parse an unversioned rid, like `win-x64`.
     pub fn from_rid(rid: &str) -> Option<Self> {
+        match Self::parse_rid(rid) {
+            Some((target, None)) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Parse a Rust target triple, like `x86_64-unknown-linux-gnu`.
+    ///
+    /// The vendor and environment parts of the triple are ignored.
+    pub fn from_triple(triple: &str) -> Option<Self> {
+        let mut parts = triple.split("-");
+
+        let arch = match parts.next() {
+            Some("x86_64") => Arch::x64,
+            Some("i686") | Some("i586") | Some("i386") => Arch::x86,
+            Some("aarch64") => Arch::arm64,
+            _ => return None,
+        };
+
+        let rest: Vec<_> = parts.collect();
+
+        if rest.contains(&"windows") {
+            Some(CrossTarget::Windows(arch))
+        } else if rest.contains(&"darwin") {
+            Some(CrossTarget::MacOS(arch))
+        } else if rest.contains(&"linux") {
+            Some(CrossTarget::Linux(arch))
+        } else if rest.contains(&"freebsd") {
+            Some(CrossTarget::FreeBSD(arch))
+        } else {
+            None
+        }
+    }
+
+    /// Parse a rid that may include an OS version, like `win10-x64` or `osx.11.0-arm64`.
+    pub fn parse_rid(rid: &str) -> Option<(Self, Option<OsVersion>)> {
         let mut parts = rid.split("-");
 
-        let platform = parts.next();
+        let platform = parts.next().and_then(parse_platform);
         let arch = parts.next().and_then(Arch::from_rid);
 
+        if parts.next().is_some() {
+            return None;
+        }
+
         platform
             .and_then(|platform| arch.map(|arch| (platform, arch)))
-            .and_then(|(platform, arch)| match platform {
-                "win" => Some(CrossTarget::Windows(arch)),
-                "osx" => Some(CrossTarget::MacOS(arch)),
-                "linux" => Some(CrossTarget::Linux(arch)),
+            .and_then(|((platform, version), arch)| match platform {
+                "win" => Some((CrossTarget::Windows(arch), version)),
+                "osx" => Some((CrossTarget::MacOS(arch), version)),
+                "linux" => Some((CrossTarget::Linux(arch), version)),
+                "freebsd" => Some((CrossTarget::FreeBSD(arch), version)),
                 _ => None,
             })
     }
 }
 
+/// The version of an operating system in a rid.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct OsVersion {
+    pub major: u32,
+    pub minor: Option<u32>,
+}
+
+impl OsVersion {
+    /// Parse a version like `10` or `11.0`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.split(".");
+
+        let major = parts.next().and_then(|major| major.parse().ok());
+        let minor = match parts.next() {
+            Some(minor) => match minor.parse() {
+                Ok(minor) => Some(Some(minor)),
+                Err(_) => None,
+            },
+            None => Some(None),
+        };
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        major.and_then(|major| {
+            minor.map(|minor| OsVersion {
+                major: major,
+                minor: minor,
+            })
+        })
+    }
+}
+
+impl fmt::Display for OsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.minor {
+            Some(minor) => write!(f, "{}.{}", self.major, minor),
+            None => write!(f, "{}", self.major),
+        }
+    }
+}
+
+/// Split the platform part of a rid into its name and optional version.
+fn parse_platform(platform: &str) -> Option<(&str, Option<OsVersion>)> {
+    // Windows versions aren't separated from the platform name
+    if platform.starts_with("win") {
+        let version = &platform["win".len()..];
+
+        return match version.len() {
+            0 => Some(("win", None)),
+            _ if version.chars().all(|c| c.is_digit(10)) => {
+                OsVersion::parse(version).map(|version| ("win", Some(version)))
+            }
+            _ => None,
+        };
+    }
+
+    match platform.find('.') {
+        Some(idx) => {
+            let (name, version) = platform.split_at(idx);
+
+            OsVersion::parse(&version[1..]).map(|version| (name, Some(version)))
+        }
+        None => Some((platform, None)),
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub enum Arch {
     x64,
     x86,
+    arm64,
 }
 
 impl Arch {
@@ -221,6 +621,7 @@ impl Arch {
         match *self {
             Arch::x86 => "x86",
             Arch::x64 => "x64",
+            Arch::arm64 => "arm64",
         }
     }
 
@@ -228,6 +629,7 @@ impl Arch {
         match rid {
             "x86" => Some(Arch::x86),
             "x64" => Some(Arch::x64),
+            "arm64" => Some(Arch::arm64),
             _ => None,
         }
     }
@@ -245,8 +647,12 @@ fn local_arch() -> Option<Arch> {
 fn local_arch() -> Option<Arch> {
     Some(Arch::x64)
 }
+#[cfg(target_arch = "aarch64")]
+fn local_arch() -> Option<Arch> {
+    Some(Arch::arm64)
+}
 
-#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
 fn local_arch() -> Option<Arch> {
     None
 }
@@ -264,15 +670,45 @@ fn local_target() -> Option<CrossTarget> {
     local_arch().map(|arch| CrossTarget::Linux(arch))
 }
 
-#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+#[cfg(target_os = "freebsd")]
+fn local_target() -> Option<CrossTarget> {
+    local_arch().map(|arch| CrossTarget::FreeBSD(arch))
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux", target_os = "freebsd")))]
 fn local_target() -> Option<CrossTarget> {
     None
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use super::*;
 
+    #[test]
+    fn rid_equal_targets_collide() {
+        let local = match CrossTarget::local() {
+            Some(local) => local,
+            None => return,
+        };
+
+        let mut targets = HashMap::new();
+        targets.insert(Target::Local, "local");
+        targets.insert(Target::Cross(local), "cross");
+
+        assert_eq!(Target::Local, Target::Cross(local));
+        assert_eq!(1, targets.len());
+        assert_eq!(Some(&"cross"), targets.get(&Target::Local));
+    }
+
+    #[test]
+    fn versioned_targets_are_distinct() {
+        let target = CrossTarget::Windows(Arch::x64);
+        let versioned = Target::Cross(target).with_version(OsVersion::parse("10").unwrap());
+
+        assert!(Target::Cross(target) != versioned);
+    }
+
     #[test]
     fn unknown_rid() {
         let target = Target::from_rid("mcnuggets");
@@ -290,6 +726,35 @@ mod tests {
         assert_eq!(rid, target.rid());
     }
 
+    #[test]
+    fn windows_x86_triple() {
+        for triple in &["i686-pc-windows-msvc", "i686-pc-windows-gnu"] {
+            let target = Target::from_triple(triple);
+
+            assert_eq!(Target::Cross(CrossTarget::Windows(Arch::x86)), target);
+            assert_eq!("win-x86", target.rid());
+        }
+    }
+
+    #[test]
+    fn other_triples() {
+        assert_eq!("win-x64", Target::from_triple("x86_64-pc-windows-msvc").rid());
+        assert_eq!("linux-x64", Target::from_triple("x86_64-unknown-linux-gnu").rid());
+        assert_eq!("osx-arm64", Target::from_triple("aarch64-apple-darwin").rid());
+        assert_eq!(Target::Unknown, Target::from_triple("wasm32-unknown-unknown"));
+    }
+
+    #[test]
+    fn freebsd_triples() {
+        let target = Target::from_triple("x86_64-unknown-freebsd");
+
+        assert_eq!(Target::Cross(CrossTarget::FreeBSD(Arch::x64)), target);
+        assert_eq!("freebsd-x64", target.rid());
+        assert_eq!("freebsd-arm64", Target::from_triple("aarch64-unknown-freebsd").rid());
+        assert_eq!(Target::Cross(CrossTarget::FreeBSD(Arch::x64)), Target::from_rid("freebsd-x64"));
+        assert!(target.is_unix());
+    }
+
     #[test]
     fn windows_x64_rid() {
         let rid = "win-x64";
@@ -339,4 +804,146 @@ mod tests {
         assert_eq!(Target::Cross(CrossTarget::Linux(Arch::x64)), target);
         assert_eq!(rid, target.rid());
     }
+
+    #[test]
+    fn osx_11_arm64_rid() {
+        let rid = "osx.11.0-arm64";
+
+        let target = Target::from_rid(rid);
+
+        let version = OsVersion {
+            major: 11,
+            minor: Some(0),
+        };
+
+        assert_eq!(Target::Versioned(CrossTarget::MacOS(Arch::arm64), version), target);
+        assert_eq!(rid, target.rid());
+    }
+
+    #[test]
+    fn win10_x64_rid() {
+        let rid = "win10-x64";
+
+        let target = Target::from_rid(rid);
+
+        let version = OsVersion {
+            major: 10,
+            minor: None,
+        };
+
+        assert_eq!(Target::Versioned(CrossTarget::Windows(Arch::x64), version), target);
+        assert_eq!(rid, target.rid());
+    }
+
+    #[test]
+    fn with_version_rid() {
+        let version = OsVersion {
+            major: 10,
+            minor: Some(12),
+        };
+
+        let target = Target::Cross(CrossTarget::MacOS(Arch::x64)).with_version(version);
+
+        assert_eq!("osx.10.12-x64", target.rid());
+    }
+
+    #[test]
+    fn win81_x64_rid() {
+        let rid = "win81-x64";
+
+        let target = Target::from_rid(rid);
+
+        let version = OsVersion {
+            major: 81,
+            minor: None,
+        };
+
+        assert_eq!(Target::Versioned(CrossTarget::Windows(Arch::x64), version), target);
+        assert_eq!(rid, target.rid());
+    }
+
+    #[test]
+    fn windows_version_with_minor_is_unknown() {
+        let version = OsVersion {
+            major: 8,
+            minor: Some(1),
+        };
+
+        let target = CrossTarget::Windows(Arch::x64);
+
+        assert_eq!(None, target.versioned_rid(version));
+        assert_eq!(Target::Unknown, Target::Cross(target).with_version(version));
+        assert!(Target::Versioned(target, version).is_unknown());
+        assert!(Target::Versioned(target, version) != Target::from_rid("win81-x64"));
+    }
+
+    #[test]
+    #[cfg(not(feature = "json"))]
+    fn quiet_requires_json_feature() {
+        for cmd in &[vec!["cargo-nuget", "pack", "--quiet"], vec!["cargo-nuget", "cross", "--targets", "win-x64", "--quiet"]] {
+            assert!(app().get_matches_from_safe(cmd.iter().cloned()).is_err());
+        }
+    }
+
+    #[test]
+    fn unversioned_cross_target_rejects_versions() {
+        assert_eq!(None, CrossTarget::from_rid("win10-x64"));
+    }
+
+    #[test]
+    fn invalid_versioned_rid() {
+        assert_eq!(Target::Unknown, Target::from_rid("osx.eleven-x64"));
+        assert_eq!(Target::Unknown, Target::from_rid("win10a-x64"));
+    }
+
+    #[test]
+    fn target_extensions() {
+        let cases = vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Some("dll")),
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), Some("so")),
+            (Target::Cross(CrossTarget::MacOS(Arch::x64)), Some("dylib")),
+            (Target::from_rid("win10-x86"), Some("dll")),
+            (Target::Unknown, None),
+        ];
+
+        for (target, extension) in cases {
+            assert_eq!(extension, target.extension(), "{:?}", target);
+        }
+    }
+
+    #[test]
+    fn target_classification() {
+        // (target, is_windows, is_unix, is_macos)
+        let cases = vec![
+            (Target::from_rid("win-x64"), true, false, false),
+            (Target::from_rid("win10-x86"), true, false, false),
+            (Target::from_rid("linux-x64"), false, true, false),
+            (Target::from_rid("osx-x64"), false, true, true),
+            (Target::from_rid("osx.11.0-arm64"), false, true, true),
+            (Target::Unknown, false, false, false),
+        ];
+
+        for (target, is_windows, is_unix, is_macos) in cases {
+            assert_eq!(is_windows, target.is_windows(), "{:?}", target);
+            assert_eq!(is_unix, target.is_unix(), "{:?}", target);
+            assert_eq!(is_macos, target.is_macos(), "{:?}", target);
+        }
+    }
+
+    #[test]
+    fn rid_graph_fallbacks() {
+        assert_eq!(
+            vec!["win", "any", "base"],
+            Target::Cross(CrossTarget::Windows(Arch::x64)).rid_graph_fallbacks()
+        );
+        assert_eq!(
+            vec!["linux", "unix-arm64", "unix", "any", "base"],
+            Target::Cross(CrossTarget::Linux(Arch::arm64)).rid_graph_fallbacks()
+        );
+        assert_eq!(
+            vec!["win-x64", "win", "any", "base"],
+            Target::from_rid("win10-x64").rid_graph_fallbacks()
+        );
+        assert_eq!(vec!["base"], Target::Unknown.rid_graph_fallbacks());
+    }
 }
@@ -8,10 +8,13 @@ use super::{CargoBuildError, CargoBuildOutput};
 use args::{Action, CrossTarget, Profile, Target};
 
 /// Args for running a `cargo` command for the native package.
+///
+/// If `ignore_missing` is set then targets without any build output are skipped instead of failing.
 #[derive(Debug, Clone, PartialEq)]
 pub struct CargoCrossBuildArgs<'a> {
     pub work_dir: Cow<'a, Path>,
     pub quiet: bool,
+    pub ignore_missing: bool,
     pub targets: HashMap<CrossTarget, CargoCrossTarget<'a>>,
 }
 
@@ -28,18 +31,21 @@ pub enum CargoCrossTarget<'a> {
 pub fn build_cross<'a>(
     args: CargoCrossBuildArgs<'a>,
 ) -> Result<Vec<CargoBuildOutput>, CargoBuildError> {
+    let ignore_missing = args.ignore_missing;
+
     args.targets
         .into_iter()
-        .map(|(target, args)| match args {
-            CargoCrossTarget::Build { .. } => Err(CargoBuildError::UnsupportedCrossBuild),
-            CargoCrossTarget::Path(path) => match path.exists() {
-                true => Ok(CargoBuildOutput {
+        .filter_map(|(target, args)| match args {
+            CargoCrossTarget::Build { .. } => Some(Err(CargoBuildError::UnsupportedCrossBuild)),
+            CargoCrossTarget::Path(path) => match (path.exists(), ignore_missing) {
+                (true, _) => Some(Ok(CargoBuildOutput {
                     path: path.into_owned(),
                     target: Target::Cross(target),
-                }),
-                false => Err(CargoBuildError::MissingOutput {
+                })),
+                (false, true) => None,
+                (false, false) => Some(Err(CargoBuildError::MissingOutput {
                     path: path.into_owned(),
-                }),
+                })),
             },
         })
         .collect::<Result<Vec<_>, CargoBuildError>>()
@@ -62,6 +68,7 @@ mod tests {
             work_dir: p.into(),
             targets: HashMap::new(),
             quiet: true,
+            ignore_missing: false,
         }
     }
 
@@ -153,4 +160,29 @@ mod tests {
             r => panic!("{:?}", r),
         }
     }
+
+    #[test]
+    fn cargo_cross_ignore_missing_output() {
+        let mut targets = HashMap::new();
+
+        targets.insert(
+            CrossTarget::Windows(Arch::x64),
+            CargoCrossTarget::Path(Cow::Owned("Cargo.toml".into())),
+        );
+        targets.insert(
+            CrossTarget::Linux(Arch::x64),
+            CargoCrossTarget::Path(Cow::Owned("not the output".into())),
+        );
+
+        let args = CargoCrossBuildArgs {
+            targets: targets,
+            ignore_missing: true,
+            ..empty_args()
+        };
+
+        let builds = build_cross(args).unwrap();
+
+        assert_eq!(1, builds.len());
+        assert_eq!(Target::Cross(CrossTarget::Windows(Arch::x64)), builds[0].target);
+    }
 }
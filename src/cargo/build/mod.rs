@@ -3,7 +3,7 @@ use std::borrow::Cow;
 use std::io::Error as IoError;
 use clap::ArgMatches;
 use args::{target_path_arg, Action, CrossTarget, Profile, Target, CARGO_BUILD_QUIET_ARG,
-           CARGO_WORK_DIR_ARG, RELEASE_ARG, TARGETS_ARG, TEST_ARG};
+           CARGO_WORK_DIR_ARG, IGNORE_MISSING_TARGETS_ARG, RELEASE_ARG, TARGETS_ARG, TEST_ARG};
 use super::CargoConfig;
 
 mod local;
@@ -24,10 +24,10 @@ impl Profile {
 
 impl CrossTarget {
     /// Get the platform specific extension for the build output.
-    fn extension(&self) -> &'static str {
+    pub fn extension(&self) -> &'static str {
         match *self {
             CrossTarget::Windows(_) => "dll",
-            CrossTarget::Linux(_) => "so",
+            CrossTarget::Linux(_) | CrossTarget::FreeBSD(_) => "so",
             CrossTarget::MacOS(_) => "dylib",
         }
     }
@@ -36,7 +36,7 @@ impl CrossTarget {
     fn prefix(&self) -> Option<&'static str> {
         match *self {
             CrossTarget::Windows(_) => None,
-            CrossTarget::Linux(_) => Some("lib"),
+            CrossTarget::Linux(_) | CrossTarget::FreeBSD(_) => Some("lib"),
             CrossTarget::MacOS(_) => Some("lib"),
         }
     }
@@ -85,7 +85,7 @@ impl<'a> From<(&'a ArgMatches<'a>, &'a CargoConfig)> for CargoLocalBuildArgs<'a>
 
         CargoLocalBuildArgs {
             work_dir: path.into(),
-            output_name: Cow::Borrowed(&cargo.name),
+            output_name: Cow::Borrowed(cargo.lib_output_name()),
             action: action,
             profile: profile,
             quiet: quiet,
@@ -121,7 +121,7 @@ impl<'a> From<(&'a ArgMatches<'a>, &'a CargoConfig)> for CargoCrossBuildArgs<'a>
                     None => CargoCrossTarget::Build {
                         action: action,
                         profile: profile,
-                        output_name: Cow::Borrowed(&cargo.name),
+                        output_name: Cow::Borrowed(cargo.lib_output_name()),
                     },
                 };
 
@@ -132,6 +132,7 @@ impl<'a> From<(&'a ArgMatches<'a>, &'a CargoConfig)> for CargoCrossBuildArgs<'a>
         CargoCrossBuildArgs {
             work_dir: path.into(),
             quiet: quiet,
+            ignore_missing: args.is_present(IGNORE_MISSING_TARGETS_ARG),
             targets: targets,
         }
     }
@@ -150,7 +151,7 @@ quick_error!{
     pub enum CargoBuildError {
         /// An io-related error reading from a file.
         Io (err: IoError) {
-            cause(err)
+            source(err)
             display("Error running cargo build\nCaused by: {}", err)
             from()
         }
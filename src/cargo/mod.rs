@@ -8,10 +8,10 @@ pub use self::build::*;
 pub use self::parse::*;
 pub use self::version::*;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use clap::ArgMatches;
 
-use args::CARGO_WORK_DIR_ARG;
+use args::{BUILD_WIDTH_ARG, CARGO_WORK_DIR_ARG, VERSION_FILE_ARG};
 
 /// Build args to parse toml from program input.
 impl<'a> From<&'a ArgMatches<'a>> for CargoParseArgs<'a> {
@@ -30,6 +30,8 @@ impl<'a> From<&'a ArgMatches<'a>> for CargoParseArgs<'a> {
 
         CargoParseArgs {
             buf: CargoBufKind::FromFile { path: path },
+            lenient: false,
+            version_file: args.value_of(VERSION_FILE_ARG).map(|path| Path::new(path).into()),
         }
     }
 }
@@ -39,6 +41,44 @@ impl<'a> From<&'a CargoConfig> for CargoLocalVersionArgs<'a> {
     fn from(cargo: &'a CargoConfig) -> Self {
         CargoLocalVersionArgs {
             version: &cargo.version,
+            build_width: None,
         }
     }
 }
+
+/// Build args to add a dev tag from program input and toml config.
+impl<'a> From<(&'a ArgMatches<'a>, &'a CargoConfig)> for CargoLocalVersionArgs<'a> {
+    fn from((args, cargo): (&'a ArgMatches<'a>, &'a CargoConfig)) -> Self {
+        CargoLocalVersionArgs {
+            // The width is validated when args are parsed
+            build_width: args.value_of(BUILD_WIDTH_ARG).and_then(|width| width.parse().ok()),
+            ..CargoLocalVersionArgs::from(cargo)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use args;
+    use super::*;
+
+    #[test]
+    fn local_version_args_build_width() {
+        let cargo = CargoConfig {
+            version: "0.1.0".into(),
+            ..CargoConfig::default()
+        };
+
+        let app = args::app();
+
+        let matches = app.clone().get_matches_from(vec!["cargo-nuget", "pack", "--build-width", "5"]);
+        let matches = matches.subcommand_matches(args::PACK_CMD).unwrap();
+        assert_eq!(Some(5), CargoLocalVersionArgs::from((matches, &cargo)).build_width);
+
+        let matches = app.clone().get_matches_from(vec!["cargo-nuget", "pack"]);
+        let matches = matches.subcommand_matches(args::PACK_CMD).unwrap();
+        assert_eq!(None, CargoLocalVersionArgs::from((matches, &cargo)).build_width);
+
+        assert!(app.get_matches_from_safe(vec!["cargo-nuget", "pack", "--build-width", "five"]).is_err());
+    }
+}
@@ -2,8 +2,12 @@ use std::str::{self, Utf8Error};
 use std::collections::BTreeMap;
 use std::io::{Error as IoError, Read};
 use std::borrow::Cow;
+use std::fmt::{Debug, Error as FmtError, Formatter};
+use std::convert::TryFrom;
 use std::fs::File;
+use std::path::{Path, PathBuf};
 use toml::{Parser, ParserError, Value};
+use semver::Version;
 
 macro_rules! toml_val {
     ($toml:ident [ $key:expr ] . $cast:ident ( )) => ({
@@ -11,76 +15,843 @@ macro_rules! toml_val {
     })
 }
 
+const UTF8_BOM: char = '\u{feff}';
+
 /// Args for parsing a `Cargo.toml` package metadata file.
 ///
-/// The source can either be a relative filepath or a byte buffer.
+/// The source can either be a relative filepath, a byte buffer or a reader.
+///
+/// In `lenient` mode, a crate that isn't a dynamic library is parsed with a warning instead of an error.
+///
+/// If a `version_file` is given then the version it contains overrides the one in the manifest.
 #[derive(Debug, PartialEq)]
 pub struct CargoParseArgs<'a> {
     pub buf: CargoBufKind<'a>,
+    pub lenient: bool,
+    pub version_file: Option<Cow<'a, Path>>,
 }
 
-#[derive(Debug, PartialEq)]
 pub enum CargoBufKind<'a> {
     FromFile { path: Cow<'a, str> },
     FromBuf { buf: Cow<'a, [u8]> },
+    FromReader { reader: Box<dyn Read + 'a> },
+}
+
+impl<'a> Debug for CargoBufKind<'a> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), FmtError> {
+        match *self {
+            CargoBufKind::FromFile { ref path } => fmt.debug_struct("FromFile").field("path", path).finish(),
+            CargoBufKind::FromBuf { ref buf } => fmt.debug_struct("FromBuf").field("buf", buf).finish(),
+            CargoBufKind::FromReader { .. } => fmt.debug_struct("FromReader").finish(),
+        }
+    }
+}
+
+/// Readers are never considered equal.
+impl<'a> PartialEq for CargoBufKind<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (&CargoBufKind::FromFile { path: ref a }, &CargoBufKind::FromFile { path: ref b }) => a == b,
+            (&CargoBufKind::FromBuf { buf: ref a }, &CargoBufKind::FromBuf { buf: ref b }) => a == b,
+            _ => false,
+        }
+    }
 }
 
 /// The parsed `Cargo.toml` metadata.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct CargoConfig {
     pub name: String,
     pub version: String,
     pub authors: Vec<String>,
+    pub keywords: Vec<String>,
+    pub categories: Vec<String>,
     pub repository: String,
     pub description: String,
+    pub homepage: Option<String>,
+    pub rust_version: Option<String>,
+    pub release_notes: Option<String>,
+    /// The `[lib].name`, if it's different from the package name.
+    pub lib_name: Option<String>,
+    /// The names of any `[[bin]]` targets.
+    pub bins: Vec<String>,
+    /// The `default-run` binary, if there's more than one.
+    pub default_run: Option<String>,
+    /// The path to the build script, if the crate has one.
+    pub build_script: Option<String>,
+    pub nuget: CargoNugetMetadata,
+    pub warnings: Vec<String>,
+}
+
+impl CargoConfig {
+    /// The name cargo uses for the lib artifact.
+    pub fn lib_output_name(&self) -> &str {
+        self.lib_name.as_ref().unwrap_or(&self.name)
+    }
+
+    /// The id to give the nuget package.
+    ///
+    /// This is the `id` from `[package.metadata.nuget]` with its `{name}` and `{version}` tokens
+    /// replaced, or the package name if there isn't one.
+    pub fn nuget_id(&self) -> Cow<'_, str> {
+        match self.nuget.id {
+            Some(ref id) => Cow::Owned(render_id(id, &self.name, &self.version).unwrap_or_else(|_| id.clone())),
+            None => Cow::Borrowed(&self.name),
+        }
+    }
+
+    /// Check whether the crate can be published as a nuget package.
+    ///
+    /// Every problem is returned, rather than stopping at the first.
+    pub fn validate(&self) -> Vec<CargoInvalidError> {
+        let mut issues = Vec::new();
+
+        if self.authors.is_empty() {
+            issues.push(CargoInvalidError::EmptyAuthors);
+        }
+
+        if self.description.trim().is_empty() {
+            issues.push(CargoInvalidError::EmptyDescription);
+        }
+
+        let id = self.nuget_id();
+        if !is_package_id(&id) {
+            issues.push(CargoInvalidError::InvalidId { id: id.into_owned() });
+        }
+
+        if Version::parse(&self.version).is_err() {
+            issues.push(CargoInvalidError::InvalidVersion {
+                version: self.version.clone(),
+            });
+        }
+
+        issues
+    }
+}
+
+/// The longest id nuget accepts for a package.
+const MAX_PACKAGE_ID_LEN: usize = 100;
+
+/// Check whether an id is valid for a nuget package, like `MyOrg.Native-Bindings`.
+///
+/// Ids are words of letters, digits and `_`, separated by single `.` or `-` characters.
+fn is_package_id(id: &str) -> bool {
+    let is_separator = |c: char| c == '.' || c == '-';
+
+    id.len() <= MAX_PACKAGE_ID_LEN
+        && id.split(is_separator)
+            .all(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+/// Check the crate can be published as a nuget package.
+///
+/// See `CargoConfig::validate` for the problems that are checked.
+pub fn check_valid(cargo: &CargoConfig) -> Result<(), CargoValidateError> {
+    let issues = cargo.validate();
+
+    match issues.is_empty() {
+        true => Ok(()),
+        false => Err(CargoValidateError::Invalid { issues: issues }),
+    }
+}
+
+/// Nuget specific metadata from the `[package.metadata.nuget]` table.
+///
+/// Package `keywords` are only merged into `tags` when `tags-from-keywords` is set,
+/// and `categories` only when `tags-from-categories` is set.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CargoNugetMetadata {
+    /// A template for the package id, like `MyOrg.{name}`.
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub project_url: Option<String>,
+    pub description_file: Option<String>,
+    pub owners: Vec<String>,
+    pub tags: Vec<String>,
+    pub tags_from_keywords: bool,
+    pub tags_from_categories: bool,
+    /// The separator to join authors and owners with, like `;`.
+    pub author_separator: Option<char>,
+    pub release_notes: Option<String>,
+    pub changelog_file: Option<String>,
 }
 
 /// Parse `CargoConfig` from the given source.
 pub fn parse_toml<'a>(args: CargoParseArgs<'a>) -> Result<CargoConfig, CargoParseError> {
-    let buf = get_buf(args.buf)?;
+    let lenient = args.lenient;
+    let version_file = args.version_file;
+
+    // Relative paths in the manifest are resolved from its directory
+    let manifest_dir = match args.buf {
+        CargoBufKind::FromFile { ref path } => manifest_dir(Path::new(path.as_ref())),
+        _ => None,
+    };
+
+    let buf = read_source(args.buf)?;
+
+    if let Some(detected) = detect_utf16(&buf) {
+        return Err(CargoParseError::WrongEncoding { detected: detected });
+    }
 
     let utf8 = str::from_utf8(&buf)?;
+
+    let files = ManifestFiles::Dir(manifest_dir.as_ref().map(|dir| dir.as_path()));
+
+    let mut config = parse_manifest(utf8, lenient, files)?;
+
+    if let Some(version_file) = version_file {
+        config.version = read_version_file(&version_file)?;
+    }
+
+    Ok(config)
+}
+
+/// Get the directory a manifest is in.
+///
+/// A manifest path like `Cargo.toml` has an empty parent, which is the current directory.
+fn manifest_dir(path: &Path) -> Option<PathBuf> {
+    path.parent().map(|dir| match dir.as_os_str().is_empty() {
+        true => PathBuf::from("."),
+        false => dir.to_path_buf(),
+    })
+}
+
+/// Parse `CargoConfig` from a manifest string without touching the filesystem.
+///
+/// Workspace keys are only resolved from the manifest itself and a `build.rs` isn't detected.
+/// Keys that refer to other files, like `description-file`, return an error.
+pub fn parse_str(toml: &str, lenient: bool) -> Result<CargoConfig, CargoParseError> {
+    parse_manifest(toml, lenient, ManifestFiles::None)
+}
+
+/// Where files referred to by a manifest are read from.
+#[derive(Debug, Clone, Copy)]
+enum ManifestFiles<'a> {
+    /// Read files relative to the manifest's directory, or the current directory.
+    Dir(Option<&'a Path>),
+    /// Don't read any files.
+    None,
+}
+
+/// Parse `CargoConfig` from a manifest string.
+fn parse_manifest(utf8: &str, lenient: bool, files: ManifestFiles) -> Result<CargoConfig, CargoParseError> {
+    // Some editors save the manifest with a byte order mark
+    let utf8 = match utf8.starts_with(UTF8_BOM) {
+        true => &utf8[UTF8_BOM.len_utf8()..],
+        false => utf8,
+    };
     let mut parser = Parser::new(utf8);
 
     let toml = parser.parse().ok_or(CargoParseError::Toml {
         errs: parser.errors,
     })?;
 
-    let is_dylib = is_dylib(&toml).unwrap_or(false);
+    let toml = resolve_workspace(toml, files)?;
 
-    if !is_dylib {
-        Err(CargoParseError::NotADyLib)?;
+    let dylib_err = match is_dylib(&toml) {
+        Ok(true) => None,
+        Ok(false) => Some(CargoParseError::NotADyLib),
+        Err(err) => Some(err),
+    };
+
+    let mut warnings = Vec::new();
+
+    if let Some(err) = dylib_err {
+        if lenient {
+            warnings.push(err.to_string());
+        } else {
+            Err(err)?;
+        }
     }
 
-    let config = parse_config_from_toml(&toml)?;
+    let mut config = parse_config_from_toml(&toml, files)?;
+    config.warnings.extend(warnings);
 
     Ok(config)
 }
 
+/// Package keys that can be inherited from `[workspace.package]`.
+const INHERITABLE_KEYS: &'static [&'static str] = &[
+    "version",
+    "authors",
+    "description",
+    "documentation",
+    "homepage",
+    "keywords",
+    "categories",
+    "license",
+    "repository",
+    "rust-version",
+];
+
+/// Replace package keys like `authors.workspace = true` with their values from `[workspace.package]`.
+///
+/// The workspace is either the manifest itself, or the nearest manifest with a
+/// `[workspace]` table in a parent directory.
+fn resolve_workspace(
+    mut toml: BTreeMap<String, Value>,
+    files: ManifestFiles,
+) -> Result<BTreeMap<String, Value>, CargoParseError> {
+    let mut pkg = match toml.get("package") {
+        Some(&Value::Table(ref pkg)) => pkg.clone(),
+        _ => return Ok(toml),
+    };
+
+    let inherits = INHERITABLE_KEYS.iter().any(|key| is_inherited(pkg.get(*key)));
+    if !inherits {
+        return Ok(toml);
+    }
+
+    let workspace = find_workspace_package(&toml, files)?;
+
+    for &key in INHERITABLE_KEYS {
+        if let Some(value) = resolve_inherited(&pkg, workspace.as_ref(), key)? {
+            pkg.insert(key.to_string(), value);
+        }
+    }
+
+    toml.insert("package".into(), Value::Table(pkg));
+
+    Ok(toml)
+}
+
+/// Resolve a single package key, taking its value from the workspace if it's inherited.
+fn resolve_inherited(
+    pkg: &BTreeMap<String, Value>,
+    workspace: Option<&BTreeMap<String, Value>>,
+    key: &'static str,
+) -> Result<Option<Value>, CargoKeyError> {
+    let value = pkg.get(key);
+
+    if !is_inherited(value) {
+        return Ok(value.cloned());
+    }
+
+    match workspace.and_then(|workspace| workspace.get(key)) {
+        Some(value) => Ok(Some(value.clone())),
+        None => Err(CargoKeyError::Missing { key: key }),
+    }
+}
+
+/// Whether a package value is a `{ workspace = true }` table.
+fn is_inherited(value: Option<&Value>) -> bool {
+    match value {
+        Some(&Value::Table(ref table)) => table.get("workspace") == Some(&Value::Boolean(true)),
+        _ => false,
+    }
+}
+
+/// Find the `[workspace.package]` table for a manifest.
+fn find_workspace_package(
+    toml: &BTreeMap<String, Value>,
+    files: ManifestFiles,
+) -> Result<Option<BTreeMap<String, Value>>, CargoParseError> {
+    fn workspace_package(toml: &BTreeMap<String, Value>) -> Option<BTreeMap<String, Value>> {
+        toml_val!(toml["workspace"].as_table()).ok().map(|workspace| {
+            toml_val!(workspace["package"].as_table())
+                .map(|pkg| pkg.clone())
+                .unwrap_or_else(|_| BTreeMap::new())
+        })
+    }
+
+    if let Some(pkg) = workspace_package(toml) {
+        return Ok(Some(pkg));
+    }
+
+    let manifest_dir = match files {
+        ManifestFiles::Dir(Some(dir)) => match dir.canonicalize() {
+            Ok(dir) => dir,
+            Err(_) => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    for dir in manifest_dir.ancestors().skip(1) {
+        let path = dir.join("Cargo.toml");
+
+        if !path.is_file() {
+            continue;
+        }
+
+        let manifest = read_manifest_file(ManifestFiles::Dir(None), &path.to_string_lossy())?;
+
+        if let Some(pkg) = Parser::new(&manifest).parse().as_ref().and_then(workspace_package) {
+            return Ok(Some(pkg));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Detect a UTF-16 byte order mark, which some editors on Windows save manifests with.
+fn detect_utf16(buf: &[u8]) -> Option<&'static str> {
+    if buf.starts_with(&[0xFF, 0xFE]) {
+        Some("UTF-16LE")
+    } else if buf.starts_with(&[0xFE, 0xFF]) {
+        Some("UTF-16BE")
+    } else {
+        None
+    }
+}
+
+/// Read a semver version from a file, ignoring surrounding whitespace.
+fn read_version_file(path: &Path) -> Result<String, CargoParseError> {
+    let mut version = String::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut version))
+        .map_err(|e| {
+            CargoParseError::Io {
+                src: path.to_string_lossy().into_owned(),
+                err: e,
+            }
+        })?;
+
+    let version = version.trim();
+
+    match Version::parse(version) {
+        Ok(_) => Ok(version.to_owned()),
+        Err(_) => Err(CargoParseError::VersionFile {
+            path: path.to_string_lossy().into_owned(),
+            version: version.to_owned(),
+        }),
+    }
+}
+
+/// Parse `CargoConfig` from a toml byte buffer.
+impl<'a> TryFrom<&'a [u8]> for CargoConfig {
+    type Error = CargoParseError;
+
+    fn try_from(buf: &'a [u8]) -> Result<Self, Self::Error> {
+        parse_toml(CargoParseArgs {
+            buf: CargoBufKind::FromBuf { buf: buf.into() },
+            lenient: false,
+            version_file: None,
+        })
+    }
+}
+
+/// Parse `CargoConfig` from a toml string without touching the filesystem.
+///
+/// This is the same as `parse_str` in strict mode.
+impl<'a> TryFrom<&'a str> for CargoConfig {
+    type Error = CargoParseError;
+
+    fn try_from(toml: &'a str) -> Result<Self, Self::Error> {
+        parse_str(toml, false)
+    }
+}
+
 /// Parse the toml tree to a `CargoConfig`.
-fn parse_config_from_toml(toml: &BTreeMap<String, Value>) -> Result<CargoConfig, CargoKeyError> {
+fn parse_config_from_toml(
+    toml: &BTreeMap<String, Value>,
+    files: ManifestFiles,
+) -> Result<CargoConfig, CargoParseError> {
     let pkg = toml_val!(toml["package"].as_table())?;
     let name = toml_val!(pkg["name"].as_str())?.to_owned();
     let ver = toml_val!(pkg["version"].as_str())?.to_owned();
     let repository = toml_val!(pkg["repository"].as_str())?.to_owned();
-    let desc = toml_val!(pkg["description"].as_str())?.to_owned();
-    let authors = toml_val!(pkg["authors"].as_slice())?
+    let authors = parse_authors(pkg)?;
+
+    let keywords = match toml_val!(pkg["keywords"].as_slice()).ok() {
+        Some(keywords) => parse_string_list(keywords),
+        None => Vec::new(),
+    };
+
+    let categories = match toml_val!(pkg["categories"].as_slice()).ok() {
+        Some(categories) => parse_string_list(categories),
+        None => Vec::new(),
+    };
+
+    // Categories aren't checked against crates.io's list, only their shape
+    let warnings = categories
         .iter()
-        .filter_map(|a| a.as_str())
-        .map(|a| a.to_owned())
+        .filter(|category| !is_category_slug(category))
+        .map(|category| format!("The category '{}' doesn't look like a crates.io category slug", category))
         .collect();
 
+    let homepage = match toml_val!(pkg["homepage"].as_str()).ok() {
+        Some(homepage) => Some(parse_url("homepage", homepage)?),
+        None => None,
+    };
+
+    let rust_version = match toml_val!(pkg["rust-version"].as_str()).ok() {
+        Some(rust_version) => Some(parse_rust_version(rust_version)?),
+        None => None,
+    };
+
+    let nuget = parse_nuget_metadata(pkg)?;
+
+    // Check the id template here so rendering it later can't fail
+    if let Some(ref id) = nuget.id {
+        render_id(id, &name, &ver).map_err(|_| CargoKeyError::Invalid {
+            key: "id",
+            value: id.to_owned(),
+        })?;
+    }
+
+    // An inline description takes precedence over a description file
+    let desc = match (toml_val!(pkg["description"].as_str()), &nuget.description_file) {
+        (Ok(desc), _) => desc.to_owned(),
+        (Err(_), &Some(ref file)) => read_manifest_file(files, file)?,
+        (Err(err), &None) => return Err(err.into()),
+    };
+
+    // Inline release notes take precedence over the changelog
+    let release_notes = match (&nuget.release_notes, &nuget.changelog_file) {
+        (&Some(ref notes), _) => Some(notes.to_owned()),
+        (&None, &Some(ref file)) => changelog_section(&read_manifest_file(files, file)?, &ver),
+        (&None, &None) => None,
+    };
+
+    let lib_name = parse_lib_name(toml, &name)?;
+    let bins = parse_bins(toml)?;
+
+    let build_script = parse_build_script(pkg, files)?;
+
+    let default_run = match pkg.get("default-run") {
+        Some(&Value::String(ref default_run)) => Some(default_run.to_owned()),
+        Some(value) => Err(CargoKeyError::Invalid {
+            key: "default-run",
+            value: value.to_string(),
+        })?,
+        None => None,
+    };
+
     Ok(CargoConfig {
         name: name,
         version: ver,
         authors: authors,
+        keywords: keywords,
+        categories: categories,
         repository: repository,
         description: desc,
+        homepage: homepage,
+        rust_version: rust_version,
+        release_notes: release_notes,
+        lib_name: lib_name,
+        bins: bins,
+        default_run: default_run,
+        build_script: build_script,
+        nuget: nuget,
+        warnings: warnings,
+    })
+}
+
+/// Check whether a category looks like a crates.io slug, like `development-tools::ffi`.
+fn is_category_slug(category: &str) -> bool {
+    category.split("::").all(|part| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    })
+}
+
+/// Parse the `[lib].name`, ignoring it if it's the same as the package name.
+fn parse_lib_name(toml: &BTreeMap<String, Value>, name: &str) -> Result<Option<String>, CargoKeyError> {
+    let lib_name = match toml_val!(toml["lib"].as_table()).ok().and_then(|lib| lib.get("name")) {
+        Some(&Value::String(ref lib_name)) => lib_name,
+        Some(value) => return Err(CargoKeyError::Invalid {
+            key: "lib.name",
+            value: value.to_string(),
+        }),
+        None => return Ok(None),
+    };
+
+    match lib_name == name {
+        true => Ok(None),
+        false => Ok(Some(lib_name.to_owned())),
+    }
+}
+
+/// Parse the `build` key, which is either a path to a build script or a bool.
+///
+/// Like cargo, a `build.rs` next to the manifest is used when the key is missing.
+fn parse_build_script(
+    pkg: &BTreeMap<String, Value>,
+    files: ManifestFiles,
+) -> Result<Option<String>, CargoKeyError> {
+    const DEFAULT_BUILD_SCRIPT: &'static str = "build.rs";
+
+    match pkg.get("build") {
+        Some(&Value::String(ref path)) => Ok(Some(path.to_owned())),
+        Some(&Value::Boolean(true)) => Ok(Some(DEFAULT_BUILD_SCRIPT.to_owned())),
+        Some(&Value::Boolean(false)) => Ok(None),
+        Some(value) => Err(CargoKeyError::Invalid {
+            key: "build",
+            value: value.to_string(),
+        }),
+        None => match files {
+            ManifestFiles::Dir(Some(dir)) if dir.join(DEFAULT_BUILD_SCRIPT).is_file() => Ok(Some(DEFAULT_BUILD_SCRIPT.to_owned())),
+            _ => Ok(None),
+        },
+    }
+}
+
+/// Parse the names of `[[bin]]` targets.
+fn parse_bins(toml: &BTreeMap<String, Value>) -> Result<Vec<String>, CargoKeyError> {
+    let bins = match toml.get("bin") {
+        Some(&Value::Array(ref bins)) => bins,
+        Some(value) => return Err(CargoKeyError::Invalid {
+            key: "bin",
+            value: value.to_string(),
+        }),
+        None => return Ok(Vec::new()),
+    };
+
+    bins.iter()
+        .map(|bin| {
+            let bin = bin.as_table().ok_or_else(|| CargoKeyError::Invalid {
+                key: "bin",
+                value: bin.to_string(),
+            })?;
+
+            toml_val!(bin["name"].as_str()).map(|name| name.to_owned())
+        })
+        .collect()
+}
+
+/// Parse the optional `[package.metadata.nuget]` table.
+fn parse_nuget_metadata(pkg: &BTreeMap<String, Value>) -> Result<CargoNugetMetadata, CargoKeyError> {
+    let nuget = match toml_val!(pkg["metadata"].as_table())
+        .ok()
+        .and_then(|metadata| toml_val!(metadata["nuget"].as_table()).ok())
+    {
+        Some(nuget) => nuget,
+        None => return Ok(CargoNugetMetadata::default()),
+    };
+
+    let project_url = match toml_val!(nuget["project-url"].as_str()).ok() {
+        Some(url) => Some(parse_url("project-url", url)?),
+        None => None,
+    };
+
+    let id = toml_val!(nuget["id"].as_str())
+        .ok()
+        .map(|id| id.to_owned());
+
+    let title = toml_val!(nuget["title"].as_str())
+        .ok()
+        .map(|title| title.to_owned());
+
+    let description_file = toml_val!(nuget["description-file"].as_str())
+        .ok()
+        .map(|file| file.to_owned());
+
+    let owners = match toml_val!(nuget["owners"].as_slice()).ok() {
+        Some(owners) => parse_string_list(owners),
+        None => Vec::new(),
+    };
+
+    let tags = match toml_val!(nuget["tags"].as_slice()).ok() {
+        Some(tags) => parse_string_list(tags),
+        None => Vec::new(),
+    };
+
+    let tags_from_keywords = toml_val!(nuget["tags-from-keywords"].as_bool()).unwrap_or(false);
+    let tags_from_categories = toml_val!(nuget["tags-from-categories"].as_bool()).unwrap_or(false);
+
+    let author_separator = match toml_val!(nuget["author-separator"].as_str()).ok() {
+        Some(separator) => {
+            let mut chars = separator.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(separator), None) => Some(separator),
+                _ => Err(CargoKeyError::Invalid {
+                    key: "author-separator",
+                    value: separator.to_owned(),
+                })?,
+            }
+        }
+        None => None,
+    };
+
+    let release_notes = toml_val!(nuget["release-notes"].as_str())
+        .ok()
+        .map(|notes| notes.to_owned());
+
+    let changelog_file = toml_val!(nuget["changelog"].as_str())
+        .ok()
+        .map(|file| file.to_owned());
+
+    Ok(CargoNugetMetadata {
+        id: id,
+        title: title,
+        project_url: project_url,
+        description_file: description_file,
+        owners: owners,
+        tags: tags,
+        tags_from_keywords: tags_from_keywords,
+        tags_from_categories: tags_from_categories,
+        author_separator: author_separator,
+        release_notes: release_notes,
+        changelog_file: changelog_file,
     })
 }
 
+/// Replace the `{name}` and `{version}` tokens in an id template.
+///
+/// Any other token is returned as an error.
+fn render_id(template: &str, name: &str, version: &str) -> Result<String, String> {
+    let mut id = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        id.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => return Err(rest.to_owned()),
+        };
+
+        match &rest[..end + 1] {
+            "{name}" => id.push_str(name),
+            "{version}" => id.push_str(version),
+            token => return Err(token.to_owned()),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    id.push_str(rest);
+
+    Ok(id)
+}
+
+/// Parse an array of strings, like owners or tags, dropping any empty entries.
+fn parse_string_list(values: &[Value]) -> Vec<String> {
+    values
+        .iter()
+        .filter_map(|v| v.as_str())
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_owned())
+        .collect()
+}
+
+/// Read a file relative to the manifest.
+fn read_manifest_file(files: ManifestFiles, file: &str) -> Result<String, CargoParseError> {
+    let path = match files {
+        ManifestFiles::Dir(Some(dir)) => dir.join(file),
+        ManifestFiles::Dir(None) => PathBuf::from(file),
+        ManifestFiles::None => return Err(CargoParseError::ExternalFile { file: file.to_owned() }),
+    };
+
+    let mut desc = String::new();
+
+    File::open(&path)
+        .and_then(|mut f| f.read_to_string(&mut desc))
+        .map_err(|e| {
+            CargoParseError::Io {
+                src: path.to_string_lossy().into_owned(),
+                err: e,
+            }
+        })?;
+
+    Ok(desc.trim_end().to_owned())
+}
+
+/// Get the section of a markdown changelog for a version.
+///
+/// The section starts at a heading that mentions the version, like `## 0.1.0` or `## [0.1.0] - 2017-05-01`,
+/// and ends at the next heading at the same level or higher.
+fn changelog_section(changelog: &str, version: &str) -> Option<String> {
+    fn heading_level(line: &str) -> Option<usize> {
+        let level = line.chars().take_while(|&c| c == '#').count();
+
+        match level {
+            0 => None,
+            level => Some(level),
+        }
+    }
+
+    let mentions_version = |line: &str| {
+        line.split(|c: char| c.is_whitespace() || c == '[' || c == ']' || c == '#')
+            .any(|word| word == version || word.trim_start_matches('v') == version)
+    };
+
+    let mut lines = changelog.lines();
+
+    let level = lines
+        .by_ref()
+        .filter_map(|line| heading_level(line).map(|level| (level, line)))
+        .find(|&(_, line)| mentions_version(line))
+        .map(|(level, _)| level)?;
+
+    let section: Vec<_> = lines
+        .take_while(|line| heading_level(line).map(|l| l > level).unwrap_or(true))
+        .collect();
+
+    let section = section.join("\n").trim().to_owned();
+
+    match section.is_empty() {
+        true => None,
+        false => Some(section),
+    }
+}
+
+/// Check that a value looks like an absolute `http` or `https` url.
+fn parse_url(key: &'static str, url: &str) -> Result<String, CargoKeyError> {
+    let host = if url.starts_with("https://") {
+        &url["https://".len()..]
+    } else if url.starts_with("http://") {
+        &url["http://".len()..]
+    } else {
+        ""
+    };
+
+    if host.is_empty() || url.chars().any(char::is_whitespace) {
+        Err(CargoKeyError::Invalid {
+            key: key,
+            value: url.to_owned(),
+        })
+    } else {
+        Ok(url.to_owned())
+    }
+}
+
+/// Check that a value looks like a partial semver version, like `1.20` or `1.20.1`.
+fn parse_rust_version(rust_version: &str) -> Result<String, CargoKeyError> {
+    let parts: Vec<_> = rust_version.split('.').collect();
+
+    let valid = parts.len() >= 2 && parts.len() <= 3
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+
+    if valid {
+        Ok(rust_version.to_owned())
+    } else {
+        Err(CargoKeyError::Invalid {
+            key: "rust-version",
+            value: rust_version.to_owned(),
+        })
+    }
+}
+
+/// Parse the package authors.
+///
+/// Authors are expected to be an array, but a single string is accepted as a single author.
+/// Nuget requires at least one author, so an empty array is invalid.
+fn parse_authors(pkg: &BTreeMap<String, Value>) -> Result<Vec<String>, CargoKeyError> {
+    if let Some(&Value::String(ref author)) = pkg.get("authors") {
+        return Ok(vec![author.to_owned()]);
+    }
+
+    let authors: Vec<_> = toml_val!(pkg["authors"].as_slice())?
+        .iter()
+        .filter_map(|a| a.as_str())
+        .map(|a| a.to_owned())
+        .collect();
+
+    if authors.is_empty() {
+        return Err(CargoKeyError::EmptyAuthors);
+    }
+
+    Ok(authors)
+}
+
 /// Get a toml byte buffer.
-fn get_buf<'a>(buf: CargoBufKind<'a>) -> Result<Cow<'a, [u8]>, CargoParseError> {
+fn read_source<'a>(buf: CargoBufKind<'a>) -> Result<Cow<'a, [u8]>, CargoParseError> {
     match buf {
         // Read the file to an owned buffer
         CargoBufKind::FromFile { path } => {
@@ -103,22 +874,49 @@ fn get_buf<'a>(buf: CargoBufKind<'a>) -> Result<Cow<'a, [u8]>, CargoParseError>
         }
         // Just use the buffer given
         CargoBufKind::FromBuf { buf } => Ok(buf),
+        // Read to the end of the reader
+        CargoBufKind::FromReader { mut reader } => {
+            let mut buf = Vec::new();
+
+            reader.read_to_end(&mut buf).map_err(|e| {
+                CargoParseError::Io {
+                    src: "reader".into(),
+                    err: e,
+                }
+            })?;
+
+            Ok(Cow::Owned(buf))
+        }
     }
 }
 
 /// Check if the toml specifies a dynamic library.
+///
+/// The `crate-type` is looked for in `[lib]`, then in `[package]`, where some manifests put it.
+/// A manifest without a `crate-type` in either is an error.
 fn is_dylib(toml: &BTreeMap<String, Value>) -> Result<bool, CargoParseError> {
-    let lib = toml_val!(toml["lib"].as_table())?;
+    let crate_type = |table: &'static str| {
+        toml_val!(toml[table].as_table())
+            .ok()
+            .and_then(|table| table.get("crate-type"))
+    };
 
-    let is_dylib = toml_val!(lib["crate-type"].as_slice())?
-        .iter()
-        .filter_map(|t| t.as_str())
-        .any(|t| t == "dylib" || t == "cdylib");
+    let crate_type = match crate_type("lib").or_else(|| crate_type("package")) {
+        Some(crate_type) => crate_type,
+        None => return Err(CargoParseError::MissingCrateType),
+    };
 
-    match is_dylib {
-        true => Ok(true),
-        _ => Ok(false),
-    }
+    let is_dylib = crate_type
+        .as_slice()
+        .map(|crate_type| {
+            crate_type
+                .iter()
+                .filter_map(|t| t.as_str())
+                .any(|t| t == "dylib" || t == "cdylib")
+        })
+        .unwrap_or(false);
+
+    Ok(is_dylib)
 }
 
 quick_error!{
@@ -128,6 +926,12 @@ quick_error!{
         Missing { key: &'static str } {
             display("The '{}' key is required, but wasn't found", key)
         }
+        Invalid { key: &'static str, value: String } {
+            display("The '{}' key has an invalid value '{}'", key, value)
+        }
+        EmptyAuthors {
+            display("The 'authors' key must contain at least one author")
+        }
     }
 }
 
@@ -137,17 +941,17 @@ quick_error!{
     pub enum CargoParseError {
         /// An io-related error reading from a file.
         Io { src: String, err: IoError } {
-            cause(err)
+            source(err)
             display("Error reading config from '{}'\nCaused by: {}", src, err)
         }
         /// An error reading the buffer as a UTF8 string.
         Utf8(err: Utf8Error) {
-            cause(err)
+            source(err)
             display("Error parsing config\nCaused by: {}", err)
             from()
         }
         Key(err: CargoKeyError){
-            cause(err)
+            source(err)
             display("Error parsing config\nCaused by: {}", err)
             from()
         }
@@ -155,135 +959,1295 @@ quick_error!{
         Toml { errs: Vec<ParserError> } {
             display("Error parsing config\nCaused by: {:?}", errs)
         }
+        /// The version file doesn't contain a valid semver version.
+        VersionFile { path: String, version: String } {
+            display("The version file '{}' contains an invalid version '{}'", path, version)
+        }
         /// The crate isn't a dynamic library.
         NotADyLib {
             display("The crate must include `dylib` in `lib.crate-type`")
         }
+        /// The crate doesn't have a `crate-type` at all.
+        MissingCrateType {
+            display("The crate must set `lib.crate-type` to include `dylib`, but it doesn't have a `crate-type`")
+        }
+        /// The manifest isn't UTF-8, like cargo expects.
+        WrongEncoding { detected: &'static str } {
+            display("The manifest looks like it's encoded as {}, but it must be UTF-8\nTry saving it as UTF-8 in your editor", detected)
+        }
+        /// The manifest refers to a file, but files can't be read.
+        ExternalFile { file: String } {
+            display("The manifest refers to the file '{}', which can't be read here", file)
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
+quick_error!{
+    /// A problem that stops a crate being published as a nuget package.
+    #[derive(Debug, PartialEq)]
+    pub enum CargoInvalidError {
+        EmptyAuthors {
+            display("The crate must have at least one author")
+        }
+        EmptyDescription {
+            display("The crate must have a description")
+        }
+        InvalidId { id: String } {
+            display("The package id '{}' isn't valid, expected words separated by '.' or '-', like 'MyOrg.Native'", id)
+        }
+        InvalidVersion { version: String } {
+            display("The version '{}' isn't a valid semver version", version)
+        }
+    }
+}
+
+quick_error!{
+    /// An error encountered because the crate can't be published as a nuget package.
+    #[derive(Debug)]
+    pub enum CargoValidateError {
+        /// The crate has one or more problems.
+        Invalid { issues: Vec<CargoInvalidError> } {
+            display("The crate can't be packed because it has {} problem(s):\n{}", issues.len(), issues.iter().map(|issue| issue.to_string()).collect::<Vec<_>>().join("\n"))
+        }
+    }
+}
+
+impl CargoInvalidError {
+    /// A stable, machine-readable code for this error.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            CargoInvalidError::EmptyAuthors => "E_CARGO_EMPTY_AUTHORS",
+            CargoInvalidError::EmptyDescription => "E_CARGO_EMPTY_DESCRIPTION",
+            CargoInvalidError::InvalidId { .. } => "E_CARGO_INVALID_ID",
+            CargoInvalidError::InvalidVersion { .. } => "E_CARGO_INVALID_VERSION",
+        }
+    }
+}
+
+impl CargoKeyError {
+    /// A stable, machine-readable code for this error.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            CargoKeyError::Missing { .. } => "E_CARGO_MISSING",
+            CargoKeyError::Invalid { .. } => "E_CARGO_INVALID",
+            CargoKeyError::EmptyAuthors => "E_CARGO_EMPTY_AUTHORS",
+        }
+    }
+}
+
+impl CargoParseError {
+    /// A stable, machine-readable code for this error.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            CargoParseError::Io { .. } => "E_CARGO_IO",
+            CargoParseError::Utf8(_) => "E_CARGO_UTF8",
+            CargoParseError::Key(ref err) => err.code(),
+            CargoParseError::Toml { .. } => "E_CARGO_TOML",
+            CargoParseError::VersionFile { .. } => "E_CARGO_VERSION_FILE",
+            CargoParseError::NotADyLib => "E_CARGO_NOT_A_DYLIB",
+            CargoParseError::MissingCrateType => "E_CARGO_MISSING_CRATE_TYPE",
+            CargoParseError::ExternalFile { .. } => "E_CARGO_EXTERNAL_FILE",
+            CargoParseError::WrongEncoding { .. } => "E_CARGO_ENCODING",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use super::*;
+
+    #[test]
     fn parse_toml_to_config() {
         let toml = r#"
             [package]
             name = "native"
             version = "0.1.0"
-            authors = ["Somebody", "Somebody Else"]
+            authors = ["Somebody", "Somebody Else"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["rlib", "dylib"]
+        "#;
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromBuf {
+                buf: toml.as_bytes().into(),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args).unwrap();
+
+        let expected = CargoConfig {
+            name: "native".into(),
+            version: "0.1.0".into(),
+            authors: vec!["Somebody".into(), "Somebody Else".into()],
+            repository: "https://github.com/KodrAus/cargo-nuget".into(),
+            description: "".into(),
+            ..CargoConfig::default()
+        };
+
+        assert_eq!(expected, toml);
+    }
+
+    #[test]
+    fn parse_toml_authors_string() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = "Somebody"
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["rlib", "dylib"]
+        "#;
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromBuf {
+                buf: toml.as_bytes().into(),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args).unwrap();
+
+        assert_eq!(vec!["Somebody".to_owned()], toml.authors);
+    }
+
+    #[test]
+    fn parse_toml_homepage() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            homepage = "https://kodraus.github.io"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(Some("https://kodraus.github.io".into()), toml.homepage);
+        assert_eq!(None, toml.nuget.project_url);
+    }
+
+    #[test]
+    fn parse_toml_categories() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+            categories = ["development-tools::ffi", "Not A Slug"]
+
+            [package.metadata.nuget]
+            tags-from-categories = true
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(vec!["development-tools::ffi".to_owned(), "Not A Slug".to_owned()], toml.categories);
+        assert!(toml.nuget.tags_from_categories);
+        assert_eq!(1, toml.warnings.len());
+    }
+
+    #[test]
+    fn parse_toml_nuget_title() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [package.metadata.nuget]
+            title = "Native Bindings"
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(Some("Native Bindings".into()), toml.nuget.title);
+    }
+
+    #[test]
+    fn parse_toml_nuget_author_separator() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [package.metadata.nuget]
+            author-separator = ";"
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(Some(';'), toml.nuget.author_separator);
+    }
+
+    #[test]
+    fn parse_toml_nuget_author_separator_not_a_char() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [package.metadata.nuget]
+            author-separator = "; "
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        match CargoConfig::try_from(toml) {
+            Err(CargoParseError::Key(CargoKeyError::Invalid { key: "author-separator", .. })) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_toml_nuget_id_template() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [package.metadata.nuget]
+            id = "MyOrg.{name}.v{version}"
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(Some("MyOrg.{name}.v{version}".into()), toml.nuget.id);
+        assert_eq!("MyOrg.native.v0.1.0", toml.nuget_id());
+    }
+
+    #[test]
+    fn parse_toml_no_nuget_id() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!("native", toml.nuget_id());
+    }
+
+    #[test]
+    fn parse_toml_nuget_project_url() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [package.metadata.nuget]
+            project-url = "https://nuget.kodraus.io"
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(None, toml.homepage);
+        assert_eq!(Some("https://nuget.kodraus.io".into()), toml.nuget.project_url);
+    }
+
+    #[test]
+    fn parse_toml_homepage_and_nuget_project_url() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            homepage = "https://kodraus.github.io"
+            description = ""
+
+            [package.metadata.nuget]
+            project-url = "https://nuget.kodraus.io"
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(Some("https://kodraus.github.io".into()), toml.homepage);
+        assert_eq!(Some("https://nuget.kodraus.io".into()), toml.nuget.project_url);
+    }
+
+    #[test]
+    fn parse_toml_nuget_owners() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [package.metadata.nuget]
+            owners = [" Someone ", "", "Someone Else", "   "]
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = parse_toml(CargoParseArgs {
+            buf: CargoBufKind::FromBuf { buf: toml.as_bytes().into() },
+            lenient: false,
+            version_file: None,
+        }).unwrap();
+
+        assert_eq!(vec!["Someone".to_owned(), "Someone Else".to_owned()], toml.nuget.owners);
+    }
+
+    #[test]
+    fn parse_changelog_section() {
+        let changelog = r#"
+# Changelog
+
+## [Unreleased]
+
+- Something new
+
+## [0.2.0] - 2017-06-01
+
+### Fixed
+
+- A bug
+
+## 0.1.0
+
+- The first release
+"#;
+
+        assert_eq!(Some("### Fixed\n\n- A bug".into()), changelog_section(changelog, "0.2.0"));
+        assert_eq!(Some("- The first release".into()), changelog_section(changelog, "0.1.0"));
+        assert_eq!(None, changelog_section(changelog, "0.3.0"));
+    }
+
+    #[test]
+    fn parse_toml_release_notes_from_changelog() {
+        let dir = TempDir::new("parse-release-notes-changelog");
+        let manifest = write_manifest(
+            &dir,
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = ["Somebody"]
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                description = ""
+
+                [package.metadata.nuget]
+                changelog = "DESC.md"
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#,
+            "## 0.1.0\n\n- Some notes\n\n## 0.0.1\n\n- Older notes\n",
+        );
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromFile {
+                path: manifest.to_string_lossy(),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args).unwrap();
+
+        assert_eq!(Some("- Some notes".into()), toml.release_notes);
+    }
+
+    /// Parse a basic manifest with the version overridden by a version file.
+    fn parse_with_version_file(name: &str, version: &str) -> Result<CargoConfig, CargoParseError> {
+        use std::io::Write;
+
+        let dir = TempDir::new(name);
+        let path = dir.join("VERSION");
+
+        File::create(&path)
+            .unwrap()
+            .write_all(version.as_bytes())
+            .unwrap();
+
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        parse_toml(CargoParseArgs {
+            buf: CargoBufKind::FromBuf { buf: toml.as_bytes().into() },
+            lenient: false,
+            version_file: Some(path.into()),
+        })
+    }
+
+    #[test]
+    fn parse_toml_version_file() {
+        let toml = parse_with_version_file("version-file", "1.2.3").unwrap();
+
+        assert_eq!("1.2.3", toml.version);
+    }
+
+    #[test]
+    fn parse_toml_version_file_trailing_whitespace() {
+        let toml = parse_with_version_file("version-file-ws", "1.2.3-beta.1 \r\n\n").unwrap();
+
+        assert_eq!("1.2.3-beta.1", toml.version);
+    }
+
+    #[test]
+    fn parse_toml_version_file_invalid() {
+        match parse_with_version_file("version-file-invalid", "one point two") {
+            Err(CargoParseError::VersionFile { ref version, .. }) if version == "one point two" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_toml_inherits_from_own_workspace() {
+        let toml = r#"
+            [workspace.package]
+            authors = ["Somebody", "Somebody Else"]
+            description = "A workspace description"
+
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = { workspace = true }
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = { workspace = true }
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(vec!["Somebody".to_owned(), "Somebody Else".to_owned()], toml.authors);
+        assert_eq!("A workspace description", toml.description);
+    }
+
+    #[test]
+    fn parse_toml_inherits_from_parent_workspace() {
+        use std::fs;
+        use std::io::Write;
+
+        let root = TempDir::new("parse-workspace");
+        fs::create_dir_all(root.join("native")).unwrap();
+
+        File::create(root.join("Cargo.toml"))
+            .unwrap()
+            .write_all(br#"
+                [workspace]
+                members = ["native"]
+
+                [workspace.package]
+                authors = ["Somebody"]
+                description = "A workspace description"
+            "#)
+            .unwrap();
+
+        let manifest = root.join("native").join("Cargo.toml");
+        File::create(&manifest)
+            .unwrap()
+            .write_all(br#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = { workspace = true }
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                description = { workspace = true }
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#)
+            .unwrap();
+
+        let toml = parse_toml(CargoParseArgs {
+            buf: CargoBufKind::FromFile {
+                path: manifest.to_string_lossy(),
+            },
+            lenient: false,
+            version_file: None,
+        }).unwrap();
+
+        assert_eq!(vec!["Somebody".to_owned()], toml.authors);
+        assert_eq!("A workspace description", toml.description);
+    }
+
+    #[test]
+    fn parse_toml_inherits_from_parent_workspace_relative_path() {
+        let toml = parse_toml(CargoParseArgs {
+            buf: CargoBufKind::FromFile {
+                path: "tests/workspace/native/Cargo.toml".into(),
+            },
+            lenient: false,
+            version_file: None,
+        }).unwrap();
+
+        assert_eq!(vec!["Somebody".to_owned()], toml.authors);
+        assert_eq!("A workspace description", toml.description);
+    }
+
+    #[test]
+    fn manifest_dir_of_relative_path() {
+        assert_eq!(Some(PathBuf::from(".")), manifest_dir(Path::new("Cargo.toml")));
+        assert_eq!(Some(PathBuf::from("native")), manifest_dir(Path::new("native/Cargo.toml")));
+    }
+
+    /// Write a manifest and description file to a fresh directory.
+    fn write_manifest(dir: &TempDir, toml: &str, desc: &str) -> PathBuf {
+        use std::io::Write;
+
+        File::create(dir.join("DESC.md"))
+            .unwrap()
+            .write_all(desc.as_bytes())
+            .unwrap();
+
+        let manifest = dir.join("Cargo.toml");
+        File::create(&manifest)
+            .unwrap()
+            .write_all(toml.as_bytes())
+            .unwrap();
+
+        manifest
+    }
+
+    #[test]
+    fn parse_toml_description_file() {
+        let dir = TempDir::new("parse-description-file");
+        let manifest = write_manifest(
+            &dir,
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = ["Somebody"]
+                repository = "https://github.com/KodrAus/cargo-nuget"
+
+                [package.metadata.nuget]
+                description-file = "DESC.md"
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#,
+            "A description from a file\n",
+        );
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromFile {
+                path: manifest.to_string_lossy(),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args).unwrap();
+
+        assert_eq!("A description from a file", toml.description);
+    }
+
+    #[test]
+    fn parse_toml_description_inline_and_file() {
+        let dir = TempDir::new("parse-description-inline-and-file");
+        let manifest = write_manifest(
+            &dir,
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = ["Somebody"]
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                description = "An inline description"
+
+                [package.metadata.nuget]
+                description-file = "DESC.md"
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#,
+            "A description from a file",
+        );
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromFile {
+                path: manifest.to_string_lossy(),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args).unwrap();
+
+        assert_eq!("An inline description", toml.description);
+    }
+
+    #[test]
+    fn parse_toml_from_file_is_valid() {
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromFile {
+                path: "tests/native/Cargo.toml".into(),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args);
+
+        assert!(toml.is_ok());
+    }
+
+    #[test]
+    fn parse_toml_from_reader() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromReader {
+                reader: Box::new(toml.as_bytes()),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args).unwrap();
+
+        assert_eq!("native", toml.name);
+    }
+
+    #[test]
+    fn parse_toml_from_failing_reader() {
+        use std::io::{self, ErrorKind};
+
+        /// A reader that fails after returning some bytes.
+        struct FailingReader {
+            read: bool,
+        }
+
+        impl Read for FailingReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.read {
+                    Err(IoError::new(ErrorKind::Other, "the reader failed"))
+                } else {
+                    self.read = true;
+                    buf[0] = b'[';
+
+                    Ok(1)
+                }
+            }
+        }
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromReader {
+                reader: Box::new(FailingReader { read: false }),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        match parse_toml(args) {
+            Err(CargoParseError::Io { .. }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_toml_cdylib_is_valid() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody", "Somebody Else"]
+            repository = "http://examplerepository.com"
+            description = ""
+
+            [lib]
+            crate-type = ["rlib", "cdylib"]
+        "#;
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromBuf {
+                buf: toml.as_bytes().into(),
+            },
+            lenient: false,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args);
+
+        assert!(toml.is_ok());
+    }
+
+    macro_rules! assert_inavlid {
+        ($input:expr, $err:pat) => ({
+            let args = CargoParseArgs {
+                buf: CargoBufKind::FromBuf { buf: $input.as_bytes().into() },
+                lenient: false,
+                version_file: None,
+            };
+
+            let toml = parse_toml(args);
+
+            match toml {
+                Err($err) => (),
+                r => panic!("{:?}", r)
+            }
+        })
+    }
+
+    #[test]
+    fn parse_toml_missing_version() {
+        assert_inavlid!(
+            r#"
+                [package]
+                name = "native"
+                authors = ["Somebody", "Somebody Else"]
+
+                [lib]
+                crate-type = ["rlib", "dylib"]
+            "#,
+            CargoParseError::Key(CargoKeyError::Missing { key: "version" })
+        );
+    }
+
+    #[test]
+    fn parse_toml_invalid_homepage() {
+        assert_inavlid!(
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = ["Somebody", "Somebody Else"]
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                homepage = "not a url"
+                description = ""
+
+                [lib]
+                crate-type = ["rlib", "dylib"]
+            "#,
+            CargoParseError::Key(CargoKeyError::Invalid { key: "homepage", .. })
+        );
+    }
+
+    #[test]
+    fn parse_toml_nuget_id_unknown_token() {
+        assert_inavlid!(
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = ["Somebody"]
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                description = ""
+
+                [package.metadata.nuget]
+                id = "MyOrg.{crate}"
+
+                [lib]
+                crate-type = ["rlib", "dylib"]
+            "#,
+            CargoParseError::Key(CargoKeyError::Invalid { key: "id", .. })
+        );
+    }
+
+    #[test]
+    fn parse_toml_invalid_rust_version() {
+        assert_inavlid!(
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = ["Somebody", "Somebody Else"]
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                rust-version = "1.x"
+                description = ""
+
+                [lib]
+                crate-type = ["rlib", "dylib"]
+            "#,
+            CargoParseError::Key(CargoKeyError::Invalid { key: "rust-version", .. })
+        );
+    }
+
+    #[test]
+    fn parse_toml_with_bom() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let with_bom = format!("\u{feff}{}", toml);
+
+        let expected = CargoConfig::try_from(toml).unwrap();
+        let actual = CargoConfig::try_from(with_bom.as_str()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn parse_toml_rust_version() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            rust-version = "1.34"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = parse_toml(CargoParseArgs {
+            buf: CargoBufKind::FromBuf { buf: toml.as_bytes().into() },
+            lenient: false,
+            version_file: None,
+        }).unwrap();
+
+        assert_eq!(Some("1.34".into()), toml.rust_version);
+    }
+
+    #[test]
+    fn parse_toml_no_rust_version() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = parse_toml(CargoParseArgs {
+            buf: CargoBufKind::FromBuf { buf: toml.as_bytes().into() },
+            lenient: false,
+            version_file: None,
+        }).unwrap();
+
+        assert_eq!(None, toml.rust_version);
+    }
+
+    #[test]
+    fn parse_toml_inherits_missing_workspace_key() {
+        assert_inavlid!(
+            r#"
+                [workspace.package]
+                authors = ["Somebody"]
+
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = ["Somebody"]
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                description = { workspace = true }
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#,
+            CargoParseError::Key(CargoKeyError::Missing { key: "description" })
+        );
+    }
+
+    #[test]
+    fn parse_toml_missing_authors() {
+        assert_inavlid!(
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                description = ""
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#,
+            CargoParseError::Key(CargoKeyError::Missing { key: "authors" })
+        );
+    }
+
+    #[test]
+    fn parse_toml_empty_authors() {
+        assert_inavlid!(
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = []
+                repository = "https://github.com/KodrAus/cargo-nuget"
+                description = ""
+
+                [lib]
+                crate-type = ["cdylib"]
+            "#,
+            CargoParseError::Key(CargoKeyError::EmptyAuthors)
+        );
+    }
+
+    #[test]
+    fn parse_toml_lib_name() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            name = "native_ffi"
+            crate-type = ["cdylib"]
+        "#;
+
+        let config = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(Some("native_ffi".to_owned()), config.lib_name);
+        assert_eq!("native_ffi", config.lib_output_name());
+    }
+
+    #[test]
+    fn parse_toml_lib_name_same_as_package() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            name = "native"
+            crate-type = ["cdylib"]
+        "#;
+
+        let config = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(None, config.lib_name);
+        assert_eq!("native", config.lib_output_name());
+    }
+
+    #[test]
+    fn parse_toml_bins() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+
+            [[bin]]
+            name = "native-tool"
+            path = "src/bin/tool.rs"
+
+            [[bin]]
+            name = "native-gen"
+        "#;
+
+        let config = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(vec!["native-tool".to_owned(), "native-gen".to_owned()], config.bins);
+    }
+
+    #[test]
+    fn parse_toml_default_run() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+            default-run = "native-tool"
+
+            [lib]
+            crate-type = ["cdylib"]
+
+            [[bin]]
+            name = "native-tool"
+
+            [[bin]]
+            name = "native-gen"
+        "#;
+
+        let config = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(Some("native-tool".to_owned()), config.default_run);
+    }
+
+    #[test]
+    fn parse_toml_no_default_run() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let config = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(None, config.default_run);
+    }
+
+    #[test]
+    fn parse_toml_invalid_default_run() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+            default-run = 1
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        match CargoConfig::try_from(toml) {
+            Err(CargoParseError::Key(CargoKeyError::Invalid { key: "default-run", .. })) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_toml_build_script_path() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
             repository = "https://github.com/KodrAus/cargo-nuget"
             description = ""
+            build = "src/build.rs"
 
             [lib]
-            crate-type = ["rlib", "dylib"]
+            crate-type = ["cdylib"]
         "#;
 
-        let args = CargoParseArgs {
-            buf: CargoBufKind::FromBuf {
-                buf: toml.as_bytes().into(),
-            },
-        };
-
-        let toml = parse_toml(args).unwrap();
-
-        let expected = CargoConfig {
-            name: "native".into(),
-            version: "0.1.0".into(),
-            authors: vec!["Somebody".into(), "Somebody Else".into()],
-            repository: "https://github.com/KodrAus/cargo-nuget".into(),
-            description: "".into(),
-        };
+        let config = CargoConfig::try_from(toml).unwrap();
 
-        assert_eq!(expected, toml);
+        assert_eq!(Some("src/build.rs".to_owned()), config.build_script);
     }
 
     #[test]
-    fn parse_toml_from_file_is_valid() {
-        let args = CargoParseArgs {
-            buf: CargoBufKind::FromFile {
-                path: "tests/native/Cargo.toml".into(),
-            },
-        };
+    fn parse_toml_build_script_disabled() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+            build = false
 
-        let toml = parse_toml(args);
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
 
-        assert!(toml.is_ok());
+        let config = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!(None, config.build_script);
     }
 
     #[test]
-    fn parse_toml_cdylib_is_valid() {
+    fn parse_toml_no_build_script() {
         let toml = r#"
             [package]
             name = "native"
             version = "0.1.0"
-            authors = ["Somebody", "Somebody Else"]
-            repository = "http://examplerepository.com"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
             description = ""
 
             [lib]
-            crate-type = ["rlib", "cdylib"]
+            crate-type = ["cdylib"]
         "#;
 
-        let args = CargoParseArgs {
-            buf: CargoBufKind::FromBuf {
-                buf: toml.as_bytes().into(),
-            },
-        };
-
-        let toml = parse_toml(args);
+        let config = CargoConfig::try_from(toml).unwrap();
 
-        assert!(toml.is_ok());
+        assert_eq!(None, config.build_script);
     }
 
-    macro_rules! assert_inavlid {
-        ($input:expr, $err:pat) => ({
-            let args = CargoParseArgs {
-                buf: CargoBufKind::FromBuf { buf: $input.as_bytes().into() }
-            };
+    #[test]
+    fn parse_toml_bin_missing_name() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
 
-            let toml = parse_toml(args);
+            [lib]
+            crate-type = ["cdylib"]
 
-            match toml {
-                Err($err) => (),
-                r => panic!("{:?}", r)
-            }
-        })
+            [[bin]]
+            path = "src/bin/tool.rs"
+        "#;
+
+        assert_inavlid!(toml, CargoParseError::Key(CargoKeyError::Missing { key: "name" }));
     }
 
     #[test]
-    fn parse_toml_missing_version() {
+    fn parse_toml_missing_name() {
         assert_inavlid!(
             r#"
                 [package]
-                name = "native"
+                version = "0.1.0"
                 authors = ["Somebody", "Somebody Else"]
 
                 [lib]
                 crate-type = ["rlib", "dylib"]
             "#,
-            CargoParseError::Key(CargoKeyError::Missing { key: "version" })
+            CargoParseError::Key(CargoKeyError::Missing { key: "name" })
         );
     }
 
     #[test]
-    fn parse_toml_missing_name() {
+    fn parse_toml_not_a_dylib() {
         assert_inavlid!(
             r#"
                 [package]
+                name = "native"
                 version = "0.1.0"
                 authors = ["Somebody", "Somebody Else"]
 
                 [lib]
-                crate-type = ["rlib", "dylib"]
+                crate-type = ["rlib", "staticlib"]
             "#,
-            CargoParseError::Key(CargoKeyError::Missing { key: "name" })
+            CargoParseError::NotADyLib
         );
     }
 
     #[test]
-    fn parse_toml_not_a_dylib() {
+    fn parse_toml_not_a_dylib_lenient() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody", "Somebody Else"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["staticlib"]
+        "#;
+
+        let args = CargoParseArgs {
+            buf: CargoBufKind::FromBuf {
+                buf: toml.as_bytes().into(),
+            },
+            lenient: true,
+            version_file: None,
+        };
+
+        let toml = parse_toml(args).unwrap();
+
+        assert_eq!("native", toml.name);
+        assert_eq!(1, toml.warnings.len());
+    }
+
+    #[test]
+    fn parse_toml_missing_lib() {
+        assert_inavlid!(
+            r#"
+                [package]
+                name = "native"
+                version = "0.1.0"
+                authors = ["Somebody", "Somebody Else"]
+            "#,
+            CargoParseError::MissingCrateType
+        );
+    }
+
+    #[test]
+    fn parse_toml_missing_crate_type() {
         assert_inavlid!(
             r#"
                 [package]
@@ -292,22 +2256,308 @@ mod tests {
                 authors = ["Somebody", "Somebody Else"]
 
                 [lib]
-                crate-type = ["rlib", "staticlib"]
+                name = "native"
             "#,
-            CargoParseError::NotADyLib
+            CargoParseError::MissingCrateType
         );
     }
 
     #[test]
-    fn parse_toml_missing_lib() {
+    fn parse_toml_package_crate_type() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+            crate-type = ["cdylib"]
+        "#;
+
+        let toml = CargoConfig::try_from(toml).unwrap();
+
+        assert_eq!("native", toml.name);
+    }
+
+    #[test]
+    fn parse_toml_package_crate_type_not_a_dylib() {
         assert_inavlid!(
             r#"
                 [package]
                 name = "native"
                 version = "0.1.0"
                 authors = ["Somebody", "Somebody Else"]
+                crate-type = ["staticlib"]
             "#,
             CargoParseError::NotADyLib
         );
     }
+
+    #[test]
+    fn try_from_str() {
+        use std::convert::TryInto;
+
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let config: CargoConfig = toml.try_into().unwrap();
+
+        assert_eq!("native", config.name);
+    }
+
+    #[test]
+    fn parse_toml_utf16() {
+        let toml = "[package]\nname = \"native\"\n";
+
+        let le: Vec<u8> = vec![0xFF, 0xFE]
+            .into_iter()
+            .chain(toml.encode_utf16().flat_map(|c| vec![c as u8, (c >> 8) as u8]))
+            .collect();
+        let be: Vec<u8> = vec![0xFE, 0xFF]
+            .into_iter()
+            .chain(toml.encode_utf16().flat_map(|c| vec![(c >> 8) as u8, c as u8]))
+            .collect();
+
+        match CargoConfig::try_from(&le[..]) {
+            Err(CargoParseError::WrongEncoding { detected: "UTF-16LE" }) => (),
+            r => panic!("{:?}", r),
+        }
+
+        match CargoConfig::try_from(&be[..]) {
+            Err(CargoParseError::WrongEncoding { detected: "UTF-16BE" }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn parse_str_without_files() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = "A native lib"
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let config = parse_str(toml, false).unwrap();
+
+        assert_eq!("native", config.name);
+        assert_eq!("A native lib", config.description);
+        assert_eq!(None, config.build_script);
+    }
+
+    #[test]
+    fn parse_str_external_file() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+
+            [package.metadata.nuget]
+            description-file = "README.md"
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        match parse_str(toml, false) {
+            Err(CargoParseError::ExternalFile { ref file }) if file == "README.md" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn try_from_bytes() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+            repository = "https://github.com/KodrAus/cargo-nuget"
+            description = ""
+
+            [lib]
+            crate-type = ["cdylib"]
+        "#;
+
+        let config = CargoConfig::try_from(toml.as_bytes()).unwrap();
+
+        assert_eq!("0.1.0", config.version);
+    }
+
+    #[test]
+    fn try_from_invalid() {
+        let toml = r#"
+            [package]
+            name = "native"
+            version = "0.1.0"
+            authors = ["Somebody"]
+
+            [lib]
+            crate-type = ["staticlib"]
+        "#;
+
+        match CargoConfig::try_from(toml) {
+            Err(CargoParseError::NotADyLib) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn validate_missing_description_and_authors() {
+        let config = CargoConfig {
+            name: "native".into(),
+            version: "0.1.0".into(),
+            ..CargoConfig::default()
+        };
+
+        let issues = config.validate();
+
+        assert_eq!(
+            vec![CargoInvalidError::EmptyAuthors, CargoInvalidError::EmptyDescription],
+            issues
+        );
+    }
+
+    #[test]
+    fn validate_non_ascii_id() {
+        let config = CargoConfig {
+            name: "nätive".into(),
+            version: "0.1.0".into(),
+            authors: vec!["Somebody".into()],
+            description: "A description".into(),
+            ..CargoConfig::default()
+        };
+
+        assert_eq!(vec![CargoInvalidError::InvalidId { id: "nätive".into() }], config.validate());
+
+        match check_valid(&config) {
+            Err(CargoValidateError::Invalid { ref issues }) if issues.len() == 1 => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn validate_id_and_version() {
+        let config = CargoConfig {
+            name: "native".into(),
+            version: "0.1".into(),
+            authors: vec!["Somebody".into()],
+            description: "A description".into(),
+            nuget: CargoNugetMetadata {
+                id: Some("MyOrg..{name}".into()),
+                ..CargoNugetMetadata::default()
+            },
+            ..CargoConfig::default()
+        };
+
+        let issues = config.validate();
+
+        assert_eq!(
+            vec![
+                CargoInvalidError::InvalidId { id: "MyOrg..native".into() },
+                CargoInvalidError::InvalidVersion { version: "0.1".into() },
+            ],
+            issues
+        );
+
+        let config = CargoConfig {
+            version: "0.1.0".into(),
+            nuget: CargoNugetMetadata::default(),
+            ..config
+        };
+
+        assert_eq!(Vec::<CargoInvalidError>::new(), config.validate());
+        assert!(check_valid(&config).is_ok());
+    }
+
+    #[test]
+    fn parse_error_codes() {
+        use std::io::ErrorKind;
+
+        let bytes = vec![0xff];
+
+        let cases = vec![
+            (
+                CargoParseError::Io {
+                    src: "Cargo.toml".into(),
+                    err: IoError::new(ErrorKind::Other, "io"),
+                },
+                "E_CARGO_IO",
+            ),
+            (
+                CargoParseError::Utf8(str::from_utf8(&bytes).unwrap_err()),
+                "E_CARGO_UTF8",
+            ),
+            (
+                CargoParseError::Key(CargoKeyError::Missing { key: "name" }),
+                "E_CARGO_MISSING",
+            ),
+            (
+                CargoParseError::Key(CargoKeyError::Invalid {
+                    key: "default-run",
+                    value: "1".into(),
+                }),
+                "E_CARGO_INVALID",
+            ),
+            (
+                CargoParseError::Key(CargoKeyError::EmptyAuthors),
+                "E_CARGO_EMPTY_AUTHORS",
+            ),
+            (CargoParseError::Toml { errs: vec![] }, "E_CARGO_TOML"),
+            (
+                CargoParseError::VersionFile {
+                    path: "VERSION".into(),
+                    version: "one point two".into(),
+                },
+                "E_CARGO_VERSION_FILE",
+            ),
+            (CargoParseError::NotADyLib, "E_CARGO_NOT_A_DYLIB"),
+            (CargoParseError::MissingCrateType, "E_CARGO_MISSING_CRATE_TYPE"),
+            (
+                CargoParseError::ExternalFile { file: "README.md".into() },
+                "E_CARGO_EXTERNAL_FILE",
+            ),
+            (
+                CargoParseError::WrongEncoding { detected: "UTF-16LE" },
+                "E_CARGO_ENCODING",
+            ),
+        ];
+
+        for (err, code) in cases {
+            assert_eq!(code, err.code());
+        }
+    }
+
+    #[test]
+    fn invalid_error_codes() {
+        let cases = vec![
+            (CargoInvalidError::EmptyAuthors, "E_CARGO_EMPTY_AUTHORS"),
+            (CargoInvalidError::EmptyDescription, "E_CARGO_EMPTY_DESCRIPTION"),
+            (CargoInvalidError::InvalidId { id: "Näive".into() }, "E_CARGO_INVALID_ID"),
+            (
+                CargoInvalidError::InvalidVersion { version: "one".into() },
+                "E_CARGO_INVALID_VERSION",
+            ),
+        ];
+
+        for (err, code) in cases {
+            assert_eq!(code, err.code());
+        }
+    }
 }
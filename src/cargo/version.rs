@@ -5,6 +5,13 @@ use semver::{Identifier, SemVerError, Version};
 #[derive(Debug, PartialEq)]
 pub struct CargoLocalVersionArgs<'a> {
     pub version: &'a str,
+    /// Zero-pad the build number to at least this many digits.
+    ///
+    /// Semver numeric identifiers can't have leading zeros, so a padded build
+    /// number is added as an alphanumeric identifier instead.
+    /// NuGet compares alphanumeric identifiers lexically, so padded build
+    /// numbers of the same width sort in the order they were produced.
+    pub build_width: Option<usize>,
 }
 
 /// A version with a dev tag added.
@@ -14,9 +21,9 @@ pub struct CargoLocalVersion {
 }
 
 pub fn local_version_tag<'a>(
-    ver: CargoLocalVersionArgs<'a>,
+    args: CargoLocalVersionArgs<'a>,
 ) -> Result<CargoLocalVersion, CargoLocalVersionError> {
-    let mut ver = Version::parse(ver.version)?;
+    let mut ver = Version::parse(args.version)?;
     let build = UTC::now().timestamp();
 
     if build < 0 {
@@ -25,7 +32,10 @@ pub fn local_version_tag<'a>(
 
     let build = build as u64;
 
-    add_pretag(&mut ver, "dev", build);
+    match args.build_width {
+        Some(width) => add_padded_pretag(&mut ver, "dev", build, width),
+        None => add_pretag(&mut ver, "dev", build),
+    }
 
     Ok(CargoLocalVersion {
         version: ver.to_string(),
@@ -42,12 +52,20 @@ fn add_pretag(ver: &mut Version, tag: &str, num: u64) {
     ver.build = vec![];
 }
 
+fn add_padded_pretag(ver: &mut Version, tag: &str, num: u64, width: usize) {
+    add_pretag(ver, tag, num);
+
+    if let Some(last) = ver.pre.last_mut() {
+        *last = Identifier::AlphaNumeric(format!("{:0width$}", num, width = width));
+    }
+}
+
 quick_error!{
 /// An error encountered while updating a semver version.
     #[derive(Debug)]
     pub enum CargoLocalVersionError {
         Parse(err: SemVerError) {
-            cause(err)
+            source(err)
             display("Error adding dev pretag\nCaused by: {}", err)
             from()
         }
@@ -57,10 +75,90 @@ quick_error!{
     }
 }
 
+impl CargoLocalVersionError {
+    /// A stable, machine-readable code for this error.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            CargoLocalVersionError::Parse(_) => "E_VERSION_PARSE",
+            CargoLocalVersionError::PreEpoch => "E_VERSION_PRE_EPOCH",
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use semver::Version;
+    use quickcheck::{Arbitrary, Gen};
+
+    /// An arbitrary valid semver version.
+    #[derive(Clone, Debug)]
+    struct ArbitraryVersion(Version);
+
+    impl Arbitrary for ArbitraryVersion {
+        fn arbitrary<G: Gen>(g: &mut G) -> Self {
+            fn identifiers<G: Gen>(g: &mut G) -> Vec<Identifier> {
+                let len = usize::arbitrary(g) % 4;
+
+                (0..len)
+                    .map(|_| match bool::arbitrary(g) {
+                        true => Identifier::Numeric(u64::arbitrary(g)),
+                        false => Identifier::AlphaNumeric(alphanumeric(g)),
+                    })
+                    .collect()
+            }
+
+            // Alphanumeric identifiers always start with a letter so they're never numeric
+            fn alphanumeric<G: Gen>(g: &mut G) -> String {
+                const LETTERS: &'static [u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+                const CHARS: &'static [u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-";
+
+                let mut ident = String::new();
+                ident.push(LETTERS[usize::arbitrary(g) % LETTERS.len()] as char);
+
+                for _ in 0..(usize::arbitrary(g) % 8) {
+                    ident.push(CHARS[usize::arbitrary(g) % CHARS.len()] as char);
+                }
+
+                ident
+            }
+
+            ArbitraryVersion(Version {
+                major: u64::arbitrary(g),
+                minor: u64::arbitrary(g),
+                patch: u64::arbitrary(g),
+                pre: identifiers(g),
+                build: identifiers(g),
+            })
+        }
+    }
+
+    /// Add a pretag and check the result parses back to the same version.
+    fn pretag_roundtrips(mut ver: Version, num: u64) -> bool {
+        add_pretag(&mut ver, "dev", num);
+
+        match Version::parse(&ver.to_string()) {
+            Ok(parsed) => {
+                parsed == ver
+                    && parsed.build.is_empty()
+                    && parsed.pre.last() == Some(&Identifier::Numeric(num))
+            }
+            Err(_) => false,
+        }
+    }
+
+    quickcheck! {
+        fn add_pretag_roundtrips(ver: ArbitraryVersion, num: u64) -> bool {
+            pretag_roundtrips(ver.0, num)
+        }
+    }
+
+    #[test]
+    fn add_pretag_roundtrips_hyphenated_pre_and_build() {
+        let ver = Version::parse("1.0.0-a-1.0.x-+b.2").unwrap();
+
+        assert!(pretag_roundtrips(ver, u64::max_value()));
+    }
 
     #[test]
     fn add_pretag_and_build() {
@@ -88,4 +186,30 @@ mod tests {
 
         assert_eq!("0.0.1-carrots.2", &ver.to_string());
     }
+
+    #[test]
+    fn add_padded_pretag_and_build() {
+        let mut ver = Version::parse("0.1.0").unwrap();
+
+        add_padded_pretag(&mut ver, "dev", 42, 5);
+
+        assert_eq!("0.1.0-dev.00042", &ver.to_string());
+    }
+
+    #[test]
+    fn add_padded_pretag_wider_than_width() {
+        let mut ver = Version::parse("0.1.0").unwrap();
+
+        add_padded_pretag(&mut ver, "dev", 123456, 5);
+
+        assert_eq!("0.1.0-dev.123456", &ver.to_string());
+    }
+
+    #[test]
+    fn local_version_error_codes() {
+        let parse = CargoLocalVersionError::from(Version::parse("not a version").unwrap_err());
+
+        assert_eq!("E_VERSION_PARSE", parse.code());
+        assert_eq!("E_VERSION_PRE_EPOCH", CargoLocalVersionError::PreEpoch.code());
+    }
 }
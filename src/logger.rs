@@ -39,9 +39,15 @@ impl Log for Logger {
     }
 }
 
-pub fn init() {
+/// Initialize the logger.
+///
+/// If `quiet` is set then only errors are logged.
+pub fn init(quiet: bool) {
     log::set_logger(|max_level| {
-        max_level.set(LogLevelFilter::Debug);
+        max_level.set(match quiet {
+            true => LogLevelFilter::Error,
+            false => LogLevelFilter::Debug,
+        });
         Box::new(Logger)
     }).unwrap();
 }
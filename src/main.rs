@@ -1,8 +1,10 @@
 // #![deny(warnings)]
 
+extern crate base64;
 extern crate chrono;
 #[macro_use]
 extern crate clap;
+extern crate flate2;
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
@@ -10,11 +12,23 @@ extern crate log;
 #[macro_use]
 extern crate quick_error;
 extern crate semver;
+#[cfg(feature = "json")]
+extern crate serde;
+#[cfg(feature = "json")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "json")]
+extern crate serde_json;
+extern crate sha2;
 extern crate term_painter;
 extern crate toml;
 extern crate xml;
 extern crate zip;
 
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
+
 #[macro_use]
 mod macros;
 
@@ -22,13 +36,19 @@ pub mod cargo;
 pub mod nuget;
 pub mod pack;
 pub mod cross;
+pub mod warnings;
+pub mod report;
 mod args;
 mod logger;
+#[cfg(test)]
+mod tempdir;
 
 use std::error::Error;
 use std::process;
 
-fn get_command(args: &clap::ArgMatches) -> Option<Result<(), Box<Error>>> {
+use report::BuildReport;
+
+fn get_command(args: &clap::ArgMatches) -> Option<Result<BuildReport, Box<dyn Error>>> {
     // Run pack command
     let pack_cmd = || args.subcommand_matches(args::PACK_CMD).map(pack::call);
 
@@ -39,10 +59,15 @@ fn get_command(args: &clap::ArgMatches) -> Option<Result<(), Box<Error>>> {
 }
 
 fn main() {
-    logger::init();
-
     let args = args::app().get_matches();
 
+    let quiet = args.subcommand()
+        .1
+        .map(|cmd| cmd.is_present(args::QUIET_ARG))
+        .unwrap_or(false);
+
+    logger::init(quiet);
+
     let mut result = BuildResult::default();
 
     if let Some(cmd) = get_command(&args) {
@@ -50,7 +75,7 @@ fn main() {
 
         match cmd {
             Err(e) => result.err = Some(e),
-            _ => (),
+            Ok(report) => result.report = Some(report),
         }
     }
 
@@ -68,17 +93,33 @@ fn main() {
 
             process::exit(1);
         }
-        BuildResult { err: None, .. } => {
+        BuildResult { err: None, report, .. } => {
             // print success and exit
             info!("\nThe build finished successfully");
 
+            if quiet {
+                print_report(report);
+            }
+
             process::exit(0);
         }
     }
 }
 
+/// Print the build report for tooling to consume.
+#[cfg(feature = "json")]
+fn print_report(report: Option<BuildReport>) {
+    if let Some(report) = report {
+        println!("{}", report.to_json());
+    }
+}
+
+#[cfg(not(feature = "json"))]
+fn print_report(_: Option<BuildReport>) {}
+
 #[derive(Default)]
 struct BuildResult {
     ran: bool,
-    err: Option<Box<Error>>,
+    err: Option<Box<dyn Error>>,
+    report: Option<BuildReport>,
 }
@@ -0,0 +1,175 @@
+//! Reuse libs that have already been read by previous packs.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::sha256_hex;
+use super::util::file;
+
+/// Identifies a lib on disk as it was when it was read.
+///
+/// A lib that's been modified since it was cached won't have the same key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackCacheKey {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    pub len: u64,
+    pub strip: bool,
+}
+
+impl PackCacheKey {
+    /// Get the key for a lib from its metadata.
+    ///
+    /// `None` is returned if the metadata can't be read.
+    pub fn for_lib(path: &Path, strip: bool) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+
+        Some(PackCacheKey {
+            path: path.to_path_buf(),
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+            strip: strip,
+        })
+    }
+}
+
+/// A store for libs that have been read and stripped, so they don't need to be read again.
+pub trait PackCache {
+    /// Get a previously read lib.
+    fn get(&mut self, key: &PackCacheKey) -> Option<Vec<u8>>;
+
+    /// Store a lib that's been read.
+    fn insert(&mut self, key: PackCacheKey, lib: &[u8]);
+}
+
+/// A cache that never stores anything.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NoPackCache;
+
+impl PackCache for NoPackCache {
+    fn get(&mut self, _: &PackCacheKey) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn insert(&mut self, _: PackCacheKey, _: &[u8]) {}
+}
+
+/// A cache that keeps libs in memory.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemoryPackCache {
+    libs: HashMap<PackCacheKey, Vec<u8>>,
+}
+
+impl PackCache for MemoryPackCache {
+    fn get(&mut self, key: &PackCacheKey) -> Option<Vec<u8>> {
+        self.libs.get(key).cloned()
+    }
+
+    fn insert(&mut self, key: PackCacheKey, lib: &[u8]) {
+        self.libs.insert(key, lib.to_vec());
+    }
+}
+
+/// A cache that keeps libs in files in a directory, so they're available across runs.
+///
+/// The cache is best-effort, so errors reading or writing files are treated as misses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirPackCache {
+    pub dir: PathBuf,
+}
+
+impl DirPackCache {
+    /// Get the file a lib is cached in.
+    ///
+    /// The name is a digest of the key, so it's the same across runs and Rust versions.
+    fn entry_path(&self, key: &PackCacheKey) -> PathBuf {
+        let modified = key.modified
+            .duration_since(UNIX_EPOCH)
+            .map(|modified| format!("{}.{:09}", modified.as_secs(), modified.subsec_nanos()))
+            .unwrap_or_default();
+
+        let key = format!("{}\0{}\0{}\0{}", key.path.to_string_lossy(), modified, key.len, key.strip);
+
+        self.dir.join(format!("{}.lib", sha256_hex(key.as_bytes())))
+    }
+}
+
+impl PackCache for DirPackCache {
+    fn get(&mut self, key: &PackCacheKey) -> Option<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        File::open(self.entry_path(key))
+            .and_then(|mut f| f.read_to_end(&mut buf))
+            .ok()
+            .map(|_| buf)
+    }
+
+    fn insert(&mut self, key: PackCacheKey, lib: &[u8]) {
+        let path = self.entry_path(&key);
+
+        // An interrupted write can't leave a truncated lib behind
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|_| file::write_with(&path, |f| f.write_all(lib)));
+
+        if let Err(e) = written {
+            debug!("lib couldn't be cached at {:?}: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use super::*;
+
+    #[test]
+    fn dir_cache_roundtrip() {
+        let dir = TempDir::new("dir-pack-cache");
+
+        let mut cache = DirPackCache { dir: dir.join("cache") };
+
+        let key = PackCacheKey::for_lib(Path::new("tests/native/Cargo.toml"), false).unwrap();
+
+        assert_eq!(None, cache.get(&key));
+
+        cache.insert(key.clone(), b"some lib");
+
+        assert_eq!(Some(b"some lib".to_vec()), cache.get(&key));
+        assert_eq!(None, cache.get(&PackCacheKey { strip: true, ..key }));
+    }
+
+    #[test]
+    fn dir_cache_insert_leaves_no_temp_file() {
+        let dir = TempDir::new("dir-pack-cache-insert");
+
+        let mut cache = DirPackCache { dir: dir.join("cache") };
+
+        let key = PackCacheKey::for_lib(Path::new("tests/native/Cargo.toml"), false).unwrap();
+        let path = cache.entry_path(&key);
+
+        cache.insert(key, b"some lib");
+
+        assert!(path.is_file());
+        assert!(!file::tmp_path(&path).exists());
+    }
+
+    #[test]
+    fn dir_cache_entry_path_is_stable() {
+        let cache = DirPackCache { dir: PathBuf::from("cache") };
+
+        let key = PackCacheKey {
+            path: PathBuf::from("some_pkg.so"),
+            modified: UNIX_EPOCH,
+            len: 4,
+            strip: false,
+        };
+
+        assert_eq!(
+            cache.dir.join("3b52417b7b3069eec2038b405ddbeb3fe4a5e03d83fc81c80cdb98cb806bd7b6.lib"),
+            cache.entry_path(&key)
+        );
+    }
+}
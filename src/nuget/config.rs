@@ -0,0 +1,211 @@
+//! Default pack options from a `nuget-rs.toml` file.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Error as IoError, Read};
+use std::path::Path;
+use toml::{Parser, ParserError, Value};
+
+use super::{NugetLibLayout, NugetPackArgs};
+
+/// Default pack options.
+///
+/// Options that aren't set leave the pack args as they are.
+/// Options given on the command line take precedence over these.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PackConfig {
+    pub compress_threshold: Option<u64>,
+    pub layout: Option<NugetLibLayout<'static>>,
+    pub strip: Option<bool>,
+    pub tags: Vec<String>,
+}
+
+impl PackConfig {
+    /// Merge these options into pack args.
+    pub fn apply<'a>(&self, args: NugetPackArgs<'a>) -> NugetPackArgs<'a> {
+        NugetPackArgs {
+            compress_threshold: self.compress_threshold.unwrap_or(args.compress_threshold),
+            layout: self.layout.clone().unwrap_or(args.layout),
+            strip: self.strip.unwrap_or(args.strip),
+            ..args
+        }
+    }
+}
+
+/// Load pack options from a toml file.
+///
+/// ```toml
+/// compress-threshold = 1024
+/// layout = "lib"
+/// tfm = "netstandard2.0"
+/// strip = true
+/// tags = ["rust", "ffi"]
+/// ```
+pub fn load_pack_config(path: &Path) -> Result<PackConfig, ConfigError> {
+    let mut buf = String::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut buf))
+        .map_err(|e| {
+            ConfigError::Io {
+                src: path.to_string_lossy().into_owned(),
+                err: e,
+            }
+        })?;
+
+    parse_pack_config(&buf)
+}
+
+fn parse_pack_config(buf: &str) -> Result<PackConfig, ConfigError> {
+    let mut parser = Parser::new(buf);
+
+    let toml = parser.parse().ok_or(ConfigError::Toml {
+        errs: parser.errors,
+    })?;
+
+    let compress_threshold = match toml.get("compress-threshold") {
+        Some(&Value::Integer(threshold)) if threshold >= 0 => Some(threshold as u64),
+        Some(value) => return Err(invalid("compress-threshold", value)),
+        None => None,
+    };
+
+    let strip = match toml.get("strip") {
+        Some(&Value::Boolean(strip)) => Some(strip),
+        Some(value) => return Err(invalid("strip", value)),
+        None => None,
+    };
+
+    let tags = match toml.get("tags") {
+        Some(&Value::Array(ref tags)) => tags.iter()
+            .filter_map(|tag| tag.as_str())
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| tag.to_owned())
+            .collect(),
+        Some(value) => return Err(invalid("tags", value)),
+        None => Vec::new(),
+    };
+
+    Ok(PackConfig {
+        compress_threshold: compress_threshold,
+        layout: parse_layout(&toml)?,
+        strip: strip,
+        tags: tags,
+    })
+}
+
+/// Parse the lib layout, where the `lib` layout requires a `tfm`.
+fn parse_layout(toml: &BTreeMap<String, Value>) -> Result<Option<NugetLibLayout<'static>>, ConfigError> {
+    let tfm = match toml.get("tfm") {
+        Some(&Value::String(ref tfm)) => Some(tfm),
+        Some(value) => return Err(invalid("tfm", value)),
+        None => None,
+    };
+
+    match (toml.get("layout"), tfm) {
+        (Some(&Value::String(ref layout)), _) if layout == "native" => Ok(Some(NugetLibLayout::Native)),
+        (Some(&Value::String(ref layout)), Some(tfm)) if layout == "lib" => Ok(Some(NugetLibLayout::Lib {
+            tfm: tfm.to_owned().into(),
+        })),
+        (Some(&Value::String(ref layout)), None) if layout == "lib" => Err(ConfigError::MissingTfm),
+        (Some(value), _) => Err(invalid("layout", value)),
+        (None, _) => Ok(None),
+    }
+}
+
+fn invalid(key: &'static str, value: &Value) -> ConfigError {
+    ConfigError::Invalid {
+        key: key,
+        value: value.to_string(),
+    }
+}
+
+quick_error!{
+    /// An error encountered while loading a pack config file.
+    #[derive(Debug)]
+    pub enum ConfigError {
+        /// An io-related error reading from a file.
+        Io { src: String, err: IoError } {
+            source(err)
+            display("Error reading pack config from '{}'\nCaused by: {}", src, err)
+        }
+        /// An error parsing the input as TOML.
+        Toml { errs: Vec<ParserError> } {
+            display("Error parsing pack config\nCaused by: {:?}", errs)
+        }
+        /// A key has an invalid value.
+        Invalid { key: &'static str, value: String } {
+            display("The '{}' key has an invalid value '{}'", key, value)
+        }
+        /// The `lib` layout was set without a `tfm`.
+        MissingTfm {
+            display("The 'lib' layout requires a 'tfm' key, like 'netstandard2.0'")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use nuget::Buf;
+    use tempdir::TempDir;
+    use super::*;
+
+    fn args<'a>(spec: &'a Buf) -> NugetPackArgs<'a> {
+        NugetPackArgs::new("some_pkg", "0.1.0", "Someone", "A description for this package", spec)
+    }
+
+    #[test]
+    fn load_and_apply_pack_config() {
+        let dir = TempDir::new("pack-config");
+        let path = dir.join("nuget-rs.toml");
+
+        File::create(&path)
+            .unwrap()
+            .write_all(br#"
+                compress-threshold = 1024
+                layout = "lib"
+                tfm = "netstandard2.0"
+                strip = true
+                tags = ["rust", "ffi"]
+            "#)
+            .unwrap();
+
+        let config = load_pack_config(&path).unwrap();
+
+        assert_eq!(vec!["rust".to_owned(), "ffi".to_owned()], config.tags);
+
+        let spec = vec![].into();
+        let args = config.apply(args(&spec));
+
+        assert_eq!(1024, args.compress_threshold);
+        assert_eq!(NugetLibLayout::Lib { tfm: "netstandard2.0".into() }, args.layout);
+        assert!(args.strip);
+    }
+
+    #[test]
+    fn empty_pack_config_keeps_args() {
+        let config = parse_pack_config("").unwrap();
+
+        let spec = vec![].into();
+
+        assert_eq!(args(&spec), config.apply(args(&spec)));
+    }
+
+    #[test]
+    fn invalid_pack_config_layout() {
+        match parse_pack_config(r#"layout = "flat""#) {
+            Err(ConfigError::Invalid { key: "layout", .. }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn pack_config_lib_layout_without_tfm() {
+        match parse_pack_config(r#"layout = "lib""#) {
+            Err(ConfigError::MissingTfm) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+}
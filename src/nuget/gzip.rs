@@ -0,0 +1,57 @@
+//! Transport compression for `nupkg`s.
+//!
+//! A `nupkg` is already a zip archive, so this is only useful for stores
+//! that expect `.nupkg.gz` files.
+
+use std::io::{Error as IoError, Read, Write};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use super::{Buf, Nupkg};
+
+/// Wrap the bytes of a `nupkg` in a gzip stream.
+pub fn gzip(nupkg: &Nupkg) -> Result<Vec<u8>, IoError> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::Default);
+
+    encoder.write_all(&nupkg.buf)?;
+
+    encoder.finish()
+}
+
+/// Unwrap the bytes of a `nupkg` from a gzip stream.
+pub fn ungzip(buf: &[u8]) -> Result<Buf, IoError> {
+    let mut decoder = GzDecoder::new(buf)?;
+
+    let mut nupkg = Vec::new();
+    decoder.read_to_end(&mut nupkg)?;
+
+    Ok(nupkg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gzip_roundtrip() {
+        let nupkg = Nupkg {
+            name: "some_pkg.0.1.0.nupkg".into(),
+            rids: vec![],
+            compressed_size: 4,
+            uncompressed_size: 4,
+            entry_stats: None,
+            buf: vec![1, 2, 3, 4].into(),
+        };
+
+        let gzipped = gzip(&nupkg).unwrap();
+
+        assert!(gzipped.starts_with(&[0x1f, 0x8b]));
+        assert_eq!(nupkg.buf, ungzip(&gzipped).unwrap());
+    }
+
+    #[test]
+    fn ungzip_invalid() {
+        assert!(ungzip(b"not gzip").is_err());
+    }
+}
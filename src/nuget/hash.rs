@@ -0,0 +1,50 @@
+//! Content hashes for `nupkg`s.
+
+use base64;
+use sha2::{Digest, Sha256, Sha512};
+
+use super::Buf;
+
+/// Get the content hash NuGet records for a package in `packages.lock.json`.
+///
+/// The hash is the standard base64 encoding of a SHA-512 digest over the
+/// complete `nupkg` archive, exactly as its bytes are written to disk.
+/// NuGet records the value without any algorithm prefix.
+pub fn lock_hash(buf: &Buf) -> String {
+    base64::encode(&Sha512::digest(buf))
+}
+
+/// Get the lowercase hex encoding of a SHA-256 digest.
+pub fn sha256_hex(buf: &[u8]) -> String {
+    Sha256::digest(buf).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_hash_known_value() {
+        let buf = Buf::from(b"abc".to_vec());
+
+        let expected = "3a81oZNherrMQXNJriBBMRLm+k6JqX6iCp7u5ktV05ohkpkqJ0/BqDa6PCOj/uu9RU1EI2Q86A4qmslPpUyknw==";
+
+        assert_eq!(expected, lock_hash(&buf));
+    }
+
+    #[test]
+    fn sha256_hex_known_value() {
+        let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        assert_eq!(expected, sha256_hex(b"abc"));
+    }
+
+    #[test]
+    fn lock_hash_empty() {
+        let buf = Buf::from(vec![]);
+
+        let expected = "z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUdBeoGlODJ6+SfaPg==";
+
+        assert_eq!(expected, lock_hash(&buf));
+    }
+}
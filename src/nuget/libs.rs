@@ -0,0 +1,176 @@
+//! Lib paths for multiple targets from a manifest file.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Error as IoError, Read};
+use std::path::{Path, PathBuf};
+
+use args::Target;
+use cargo::CargoBuildOutput;
+
+/// Load lib paths for targets from a manifest file, like a `@libs.txt` response file.
+///
+/// Each line is a `rid=path` pair. Blank lines and lines starting with `#` are ignored.
+/// Relative paths are used as they are, so they're relative to the working directory.
+///
+/// ```text
+/// # libs built by CI
+/// win-x64=artifacts/win-x64/native.dll
+/// linux-x64=artifacts/linux-x64/libnative.so
+/// ```
+pub fn parse_lib_manifest(path: &Path) -> Result<HashMap<Target, PathBuf>, LibManifestError> {
+    let mut buf = String::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut buf))
+        .map_err(|e| {
+            LibManifestError::Io {
+                src: path.to_string_lossy().into_owned(),
+                err: e,
+            }
+        })?;
+
+    parse_lib_lines(&buf)
+}
+
+/// Load prebuilt libs from a manifest file to pack instead of building.
+pub fn read_lib_manifest(path: &Path) -> Result<Vec<CargoBuildOutput>, LibManifestError> {
+    let libs = parse_lib_manifest(path)?;
+
+    Ok(libs.into_iter()
+        .map(|(target, path)| CargoBuildOutput { target: target, path: path })
+        .collect())
+}
+
+/// The path to a lib manifest given as a response file, like `@libs.txt`.
+///
+/// The leading `@` is optional.
+pub fn lib_manifest_path(arg: &str) -> &Path {
+    match arg.starts_with('@') {
+        true => Path::new(&arg[1..]),
+        false => Path::new(arg),
+    }
+}
+
+fn parse_lib_lines(buf: &str) -> Result<HashMap<Target, PathBuf>, LibManifestError> {
+    let mut libs = HashMap::new();
+
+    for (i, line) in buf.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, '=').map(str::trim);
+
+        let (rid, path) = match (parts.next(), parts.next()) {
+            (Some(rid), Some(path)) if !rid.is_empty() && !path.is_empty() => (rid, path),
+            _ => {
+                return Err(LibManifestError::Malformed {
+                    line: i + 1,
+                    text: line.to_owned(),
+                })
+            }
+        };
+
+        let target = Target::from_rid(rid);
+
+        if target.is_unknown() {
+            return Err(LibManifestError::UnknownRid {
+                line: i + 1,
+                rid: rid.to_owned(),
+            });
+        }
+
+        if libs.insert(target, PathBuf::from(path)).is_some() {
+            return Err(LibManifestError::DuplicateRid {
+                line: i + 1,
+                rid: rid.to_owned(),
+            });
+        }
+    }
+
+    Ok(libs)
+}
+
+quick_error!{
+    /// An error encountered while loading a lib manifest.
+    #[derive(Debug)]
+    pub enum LibManifestError {
+        /// An io-related error reading from a file.
+        Io { src: String, err: IoError } {
+            source(err)
+            display("Error reading lib manifest from '{}'\nCaused by: {}", src, err)
+        }
+        /// A line isn't a `rid=path` pair.
+        Malformed { line: usize, text: String } {
+            display("Line {} of the lib manifest '{}' isn't a 'rid=path' pair", line, text)
+        }
+        /// A line has a rid that isn't known.
+        UnknownRid { line: usize, rid: String } {
+            display("Line {} of the lib manifest has an unknown rid '{}'", line, rid)
+        }
+        /// A line has a rid that was already given.
+        DuplicateRid { line: usize, rid: String } {
+            display("Line {} of the lib manifest has the rid '{}' which was already given", line, rid)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use args::{Arch, CrossTarget};
+    use tempdir::TempDir;
+    use super::*;
+
+    #[test]
+    fn load_lib_manifest() {
+        let dir = TempDir::new("lib-manifest");
+        let path = dir.join("libs.txt");
+
+        File::create(&path)
+            .unwrap()
+            .write_all(b"# libs\nwin-x64 = artifacts/native.dll\n\nlinux-x64=artifacts/libnative.so\n")
+            .unwrap();
+
+        let libs = parse_lib_manifest(&path).unwrap();
+
+        assert_eq!(2, libs.len());
+        assert_eq!(
+            Some(&PathBuf::from("artifacts/native.dll")),
+            libs.get(&Target::Cross(CrossTarget::Windows(Arch::x64)))
+        );
+        assert_eq!(
+            Some(&PathBuf::from("artifacts/libnative.so")),
+            libs.get(&Target::Cross(CrossTarget::Linux(Arch::x64)))
+        );
+    }
+
+    #[test]
+    fn lib_manifest_invalid_lines() {
+        match parse_lib_lines("win-x64=native.dll\nlinux-x64 native.so") {
+            Err(LibManifestError::Malformed { line: 2, ref text }) if text == "linux-x64 native.so" => (),
+            r => panic!("{:?}", r),
+        }
+
+        match parse_lib_lines("beos-x64=native.so") {
+            Err(LibManifestError::UnknownRid { line: 1, ref rid }) if rid == "beos-x64" => (),
+            r => panic!("{:?}", r),
+        }
+
+        match parse_lib_lines("win-x64=native.dll\nwin-x64=other.dll") {
+            Err(LibManifestError::DuplicateRid { line: 2, ref rid }) if rid == "win-x64" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn lib_manifest_response_file_path() {
+        assert_eq!(Path::new("libs.txt"), lib_manifest_path("@libs.txt"));
+        assert_eq!(Path::new("libs.txt"), lib_manifest_path("libs.txt"));
+        assert_eq!(Path::new("@libs.txt"), lib_manifest_path("@@libs.txt"));
+    }
+}
@@ -3,28 +3,43 @@
 mod spec;
 mod pack;
 mod save;
+mod hash;
+mod verify;
+mod version;
+mod gzip;
+mod config;
+mod unpack;
+mod cache;
+mod libs;
 
 mod util;
 
 pub use self::spec::*;
 pub use self::pack::*;
 pub use self::save::*;
+pub use self::hash::*;
+pub use self::verify::*;
+pub use self::version::*;
+pub use self::gzip::*;
+pub use self::config::*;
+pub use self::unpack::*;
+pub use self::cache::*;
+pub use self::libs::*;
 
 use std::path::PathBuf;
 use std::fmt::{Debug, Error as FmtError, Formatter};
-use std::collections::HashMap;
 use std::borrow::Cow;
 use std::ops::Deref;
 use clap::ArgMatches;
 
 use cargo::{CargoBuildOutput, CargoConfig};
-use args::NUPKG_DIR_ARG;
+use args::{Target, ALLOW_PRERELEASE_DEPS_ARG, COMPRESSION_ARG, NO_STRIP_ARG, NUPKG_DIR_ARG, PACK_ONLY_TARGETS_ARG, STRIP_ARG};
 
 /// A wrapper around an owned byte buffer.
 ///
 /// This type basically only exists so buffer contents aren't printed
 /// in `Debug` output.
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Buf(Vec<u8>);
 
 impl From<Vec<u8>> for Buf {
@@ -50,34 +65,123 @@ impl Debug for Buf {
 /// Build args to format a nuspec from cargo toml.
 impl<'a> From<&'a CargoConfig> for NugetSpecArgs<'a> {
     fn from(cargo: &'a CargoConfig) -> Self {
+        // Keywords are only used as tags when they're opted into
+        let keywords: &[String] = match cargo.nuget.tags_from_keywords {
+            true => &cargo.keywords,
+            false => &[],
+        };
+
+        let categories: &[String] = match cargo.nuget.tags_from_categories {
+            true => &cargo.categories,
+            false => &[],
+        };
+
         NugetSpecArgs {
-            id: Cow::Borrowed(&cargo.name),
+            id: cargo.nuget_id(),
             version: Cow::Borrowed(&cargo.version),
-            authors: Cow::Owned((&cargo.authors).join(", ")),
+            authors: cargo.authors.iter().map(|author| Cow::Borrowed(author.as_str())).collect(),
+            owners: cargo.nuget.owners.iter().map(|owner| Cow::Borrowed(owner.as_str())).collect(),
             description: Cow::Borrowed(&cargo.description),
             repository: Cow::Borrowed(&cargo.repository),
+            project_url: cargo
+                .nuget
+                .project_url
+                .as_ref()
+                .or(cargo.homepage.as_ref())
+                .map(|url| Cow::Borrowed(url.as_str())),
+            language: None,
+            serviceable: false,
+            min_client_version: None,
+            release_notes: cargo.release_notes.as_ref().map(|notes| Cow::Borrowed(notes.as_str())),
+            tags: merge_tags(
+                keywords
+                    .iter()
+                    .chain(categories)
+                    .chain(&cargo.nuget.tags)
+                    .map(|tag| tag.as_str()),
+            ),
             dependencies: NugetDependencies::default(),
+            framework_references: Vec::new(),
+            extra_metadata: Vec::new(),
+            max_description_len: DEFAULT_MAX_DESCRIPTION_LEN,
+            package_types: Vec::new(),
+            allow_prerelease_deps: false,
+            files: Vec::new(),
+            author_separator: cargo.nuget.author_separator.unwrap_or(DEFAULT_AUTHOR_SEPARATOR),
+            title: cargo.nuget.title.as_ref().map(|title| Cow::Borrowed(title.as_str())),
         }
     }
 }
 
+/// Build args to format a nuspec from program input and cargo toml.
+impl<'a> From<(&'a ArgMatches<'a>, &'a CargoConfig)> for NugetSpecArgs<'a> {
+    fn from((args, cargo): (&'a ArgMatches<'a>, &'a CargoConfig)) -> Self {
+        NugetSpecArgs {
+            allow_prerelease_deps: args.is_present(ALLOW_PRERELEASE_DEPS_ARG),
+            ..NugetSpecArgs::from(cargo)
+        }
+    }
+}
+
+impl<'a> Nuspec<'a> {
+    /// Format a `nuspec` from cargo toml.
+    pub fn from_config(cargo: &'a CargoConfig) -> Result<Self, NugetSpecError> {
+        spec(NugetSpecArgs::from(cargo))
+    }
+
+    /// Build args to pack a nupkg from this nuspec and cargo build.
+    pub fn pack_args<I>(&'a self, builds: I) -> NugetPackArgs<'a>
+    where
+        I: IntoIterator<Item = &'a CargoBuildOutput>,
+    {
+        NugetPackArgs::from((self, builds))
+    }
+}
+
 /// Build args to pack a nupkg from nuspec and cargo build.
 impl<'a, I> From<(&'a Nuspec<'a>, I)> for NugetPackArgs<'a>
 where
     I: IntoIterator<Item = &'a CargoBuildOutput>,
 {
     fn from((nuspec, builds): (&'a Nuspec, I)) -> Self {
-        let mut libs = HashMap::new();
+        let libs = builds
+            .into_iter()
+            .map(|build| (build.target, build.path.as_path()));
 
-        for build in builds {
-            libs.insert(build.target, Cow::Borrowed(build.path.as_ref()));
-        }
+        NugetPackArgs::new(&*nuspec.id, &*nuspec.version, &*nuspec.authors, &*nuspec.description, &nuspec.xml)
+            .with_libs(libs)
+    }
+}
+
+/// Build args to pack a nupkg from program input, pack config, nuspec and cargo build.
+///
+/// Program input takes precedence over the pack config.
+impl<'a, I> From<(&'a ArgMatches<'a>, &'a PackConfig, &'a Nuspec<'a>, I)> for NugetPackArgs<'a>
+where
+    I: IntoIterator<Item = &'a CargoBuildOutput>,
+{
+    fn from((args, config, nuspec, builds): (&'a ArgMatches<'a>, &'a PackConfig, &'a Nuspec, I)) -> Self {
+        let base = config.apply(NugetPackArgs::from((nuspec, builds)));
+
+        let strip = match (args.is_present(STRIP_ARG), args.is_present(NO_STRIP_ARG)) {
+            (true, _) => true,
+            (_, true) => false,
+            _ => base.strip,
+        };
+
+        // The method is one of the arg's possible values
+        let compression = match args.value_of(COMPRESSION_ARG) {
+            Some("bzip2") => NugetCompression::Bzip2,
+            Some(_) => NugetCompression::Deflated,
+            None => base.compression,
+        };
 
         NugetPackArgs {
-            id: Cow::Borrowed(&nuspec.id),
-            version: Cow::Borrowed(&nuspec.version),
-            spec: &nuspec.xml,
-            cargo_libs: libs,
+            strip: strip,
+            compression: compression,
+            only: args.values_of(PACK_ONLY_TARGETS_ARG)
+                .map(|rids| rids.map(Target::from_rid).collect()),
+            ..base
         }
     }
 }
@@ -98,3 +202,233 @@ impl<'a> From<(&'a ArgMatches<'a>, &'a Nupkg<'a>)> for NugetSaveArgs<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use args;
+    use cargo::CargoNugetMetadata;
+    use super::*;
+
+    #[test]
+    fn spec_args_project_url_from_homepage() {
+        let cargo = CargoConfig {
+            homepage: Some("https://kodraus.github.io".into()),
+            ..CargoConfig::default()
+        };
+
+        let args = NugetSpecArgs::from(&cargo);
+
+        assert_eq!(Some("https://kodraus.github.io".into()), args.project_url);
+    }
+
+    #[test]
+    fn spec_args_project_url_prefers_nuget_metadata() {
+        let cargo = CargoConfig {
+            homepage: Some("https://kodraus.github.io".into()),
+            nuget: CargoNugetMetadata {
+                project_url: Some("https://nuget.kodraus.io".into()),
+                ..CargoNugetMetadata::default()
+            },
+            ..CargoConfig::default()
+        };
+
+        let args = NugetSpecArgs::from(&cargo);
+
+        assert_eq!(Some("https://nuget.kodraus.io".into()), args.project_url);
+    }
+
+    #[test]
+    fn spec_args_no_package_types() {
+        let cargo = CargoConfig::default();
+
+        let args = NugetSpecArgs::from(&cargo);
+
+        assert!(args.package_types.is_empty());
+    }
+
+    #[test]
+    fn spec_args_author_separator() {
+        let cargo = CargoConfig {
+            authors: vec!["Someone".into(), "Someone Else".into()],
+            nuget: CargoNugetMetadata {
+                owners: vec!["An Owner".into(), "Another Owner".into()],
+                ..CargoNugetMetadata::default()
+            },
+            ..CargoConfig::default()
+        };
+
+        let args = NugetSpecArgs::from(&cargo);
+        assert_eq!(vec!["Someone", "Someone Else"], args.authors);
+        assert_eq!(vec!["An Owner", "Another Owner"], args.owners);
+        assert_eq!(DEFAULT_AUTHOR_SEPARATOR, args.author_separator);
+
+        let cargo = CargoConfig {
+            nuget: CargoNugetMetadata {
+                author_separator: Some(';'),
+                ..cargo.nuget.clone()
+            },
+            ..cargo.clone()
+        };
+
+        let nuspec = Nuspec::from_config(&cargo).unwrap();
+        assert_eq!("Someone; Someone Else", nuspec.authors);
+    }
+
+    #[test]
+    fn spec_args_tags_ignore_keywords_by_default() {
+        let cargo = CargoConfig {
+            keywords: vec!["ffi".into()],
+            nuget: CargoNugetMetadata {
+                tags: vec!["Rust".into()],
+                ..CargoNugetMetadata::default()
+            },
+            ..CargoConfig::default()
+        };
+
+        let args = NugetSpecArgs::from(&cargo);
+
+        assert_eq!(vec!["Rust"], args.tags);
+    }
+
+    #[test]
+    fn spec_args_tags_from_keywords() {
+        let cargo = CargoConfig {
+            keywords: vec!["rust".into(), "ffi".into()],
+            nuget: CargoNugetMetadata {
+                tags: vec!["Rust".into()],
+                tags_from_keywords: true,
+                ..CargoNugetMetadata::default()
+            },
+            ..CargoConfig::default()
+        };
+
+        let args = NugetSpecArgs::from(&cargo);
+
+        assert_eq!(vec!["rust", "ffi"], args.tags);
+    }
+
+    #[test]
+    fn spec_args_tags_from_categories() {
+        let cargo = CargoConfig {
+            keywords: vec!["ffi".into()],
+            categories: vec!["development-tools::ffi".into()],
+            nuget: CargoNugetMetadata {
+                tags: vec!["Rust".into()],
+                ..CargoNugetMetadata::default()
+            },
+            ..CargoConfig::default()
+        };
+
+        let args = NugetSpecArgs::from(&cargo);
+        assert_eq!(vec!["Rust"], args.tags);
+
+        let cargo = CargoConfig {
+            nuget: CargoNugetMetadata {
+                tags_from_categories: true,
+                ..cargo.nuget.clone()
+            },
+            ..cargo.clone()
+        };
+
+        let args = NugetSpecArgs::from(&cargo);
+        assert_eq!(vec!["development-tools::ffi", "Rust"], args.tags);
+    }
+
+    #[test]
+    fn pack_args_from_config() {
+        let cargo = CargoConfig {
+            name: "native".into(),
+            version: "0.1.0".into(),
+            authors: vec!["Someone".into()],
+            repository: "https://github.com/KodrAus/cargo-nuget".into(),
+            description: "A description".into(),
+            ..CargoConfig::default()
+        };
+
+        let builds = vec![CargoBuildOutput {
+            path: PathBuf::from("tests/native/Cargo.toml"),
+            target: Target::Local,
+        }];
+
+        let nuspec = Nuspec::from_config(&cargo).unwrap();
+        let args = nuspec.pack_args(&builds);
+
+        assert_eq!("native", args.id);
+        assert_eq!("0.1.0", args.version);
+        assert_eq!(&nuspec.xml, args.spec);
+        assert_eq!(1, args.cargo_libs.len());
+    }
+
+    #[test]
+    fn pack_args_strip_precedence() {
+        let cargo = CargoConfig::default();
+        let nuspec = Nuspec::from_config(&cargo).unwrap();
+
+        let builds: Vec<CargoBuildOutput> = vec![];
+
+        let config = PackConfig {
+            strip: Some(true),
+            ..PackConfig::default()
+        };
+
+        let app = args::app();
+
+        let strip = |cmd: &[&str], config: &PackConfig| {
+            let matches = app.clone().get_matches_from(cmd.iter().cloned());
+            let matches = matches.subcommand_matches(args::PACK_CMD).unwrap();
+
+            NugetPackArgs::from((matches, config, &nuspec, &builds)).strip
+        };
+
+        assert!(strip(&["cargo-nuget", "pack"], &config));
+        assert!(!strip(&["cargo-nuget", "pack", "--no-strip"], &config));
+        assert!(strip(&["cargo-nuget", "pack", "--strip"], &PackConfig::default()));
+        assert!(!strip(&["cargo-nuget", "pack"], &PackConfig::default()));
+    }
+
+    #[test]
+    fn pack_args_compression() {
+        let cargo = CargoConfig::default();
+        let nuspec = Nuspec::from_config(&cargo).unwrap();
+
+        let builds: Vec<CargoBuildOutput> = vec![];
+        let config = PackConfig::default();
+
+        let app = args::app();
+
+        let compression = |cmd: &[&str]| {
+            let matches = app.clone().get_matches_from(cmd.iter().cloned());
+            let matches = matches.subcommand_matches(args::PACK_CMD).unwrap();
+
+            NugetPackArgs::from((matches, &config, &nuspec, &builds)).compression
+        };
+
+        assert_eq!(NugetCompression::Deflated, compression(&["cargo-nuget", "pack"]));
+        assert_eq!(NugetCompression::Bzip2, compression(&["cargo-nuget", "pack", "--compression", "bzip2"]));
+        assert_eq!(NugetCompression::Deflated, compression(&["cargo-nuget", "pack", "--compression", "deflated"]));
+    }
+
+    #[test]
+    fn pack_args_only_targets() {
+        use args::{Arch, CrossTarget};
+
+        let cargo = CargoConfig::default();
+        let nuspec = Nuspec::from_config(&cargo).unwrap();
+
+        let builds: Vec<CargoBuildOutput> = vec![];
+        let config = PackConfig::default();
+
+        let app = args::app();
+
+        let matches = app.clone().get_matches_from(vec!["cargo-nuget", "pack", "--pack-only-targets", "win-x64"]);
+        let matches = matches.subcommand_matches(args::PACK_CMD).unwrap();
+
+        assert_eq!(
+            Some(vec![Target::Cross(CrossTarget::Windows(Arch::x64))]),
+            NugetPackArgs::from((matches, &config, &nuspec, &builds)).only
+        );
+
+        let unknown = app.get_matches_from_safe(vec!["cargo-nuget", "cross", "--targets", "win-x64", "--pack-only-targets", "beos-x64"]);
+        assert!(unknown.is_err());
+    }
+}
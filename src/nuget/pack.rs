@@ -1,236 +1,2508 @@
-use std::io::{copy, Cursor, Error as IoError, Seek, Write};
-use std::fs::File;
+use std::io::{Cursor, Error as IoError, Read, Seek, Write};
+use std::fs::{self, File};
+use std::str::{self, Utf8Error};
 use std::path::{Path, PathBuf};
 use std::borrow::Cow;
 use std::collections::HashMap;
-use zip::CompressionMethod;
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
+use zip::{CompressionMethod, ZipArchive};
 use zip::write::{FileOptions, ZipWriter};
 use zip::result::ZipError;
 
-use super::Buf;
-use super::util::{openxml, xml};
-use args::Target;
+use super::{sha256_hex, Buf, NoPackCache, PackCache, PackCacheKey};
+use super::util::{binary, openxml, strip, xml};
+use args::{Arch, Target};
 
 /// Args for building a `nupkg` with potentially multiple targets.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct NugetPackArgs<'a> {
     pub id: Cow<'a, str>,
     pub version: Cow<'a, str>,
+    pub authors: Cow<'a, str>,
+    pub description: Cow<'a, str>,
     pub spec: &'a Buf,
-    pub cargo_libs: HashMap<Target, Cow<'a, Path>>,
+    pub cargo_libs: NugetLibs<'a>,
+    pub layout: NugetLibLayout<'a>,
+    pub compress_threshold: u64,
+    pub allow_empty: bool,
+    pub check_arch: bool,
+    pub strip: bool,
+    pub only: Option<Vec<Target>>,
+    pub headers: Vec<Cow<'a, Path>>,
+    pub compression: NugetCompression,
+    /// Write a `runtime.json` describing the packed rids and their fallbacks.
+    pub runtime_json: bool,
+    /// A `Cargo.toml` to embed in the package for provenance.
+    pub embed_manifest: Option<Cow<'a, Path>>,
+    /// Record the size of each entry before and after compression.
+    pub entry_stats: bool,
+}
+
+impl<'a> NugetPackArgs<'a> {
+    /// Args to pack a `nuspec` with no libs and the default options.
+    pub fn new<I, V, A, D>(id: I, version: V, authors: A, description: D, spec: &'a Buf) -> Self
+    where
+        I: Into<Cow<'a, str>>,
+        V: Into<Cow<'a, str>>,
+        A: Into<Cow<'a, str>>,
+        D: Into<Cow<'a, str>>,
+    {
+        NugetPackArgs {
+            id: id.into(),
+            version: version.into(),
+            authors: authors.into(),
+            description: description.into(),
+            spec: spec,
+            cargo_libs: NugetLibs::default(),
+            layout: NugetLibLayout::default(),
+            compress_threshold: DEFAULT_COMPRESS_THRESHOLD,
+            allow_empty: false,
+            check_arch: false,
+            strip: false,
+            only: None,
+            headers: Vec::new(),
+            compression: NugetCompression::default(),
+            runtime_json: false,
+            embed_manifest: None,
+            entry_stats: false,
+        }
+    }
+
+    /// Set the libs to pack from pairs of targets and paths.
+    ///
+    /// See `NugetLibs` for how the pairs are collected.
+    pub fn with_libs<I, P>(mut self, libs: I) -> Self
+    where
+        I: IntoIterator<Item = (Target, P)>,
+        P: Into<Cow<'a, Path>>,
+    {
+        self.cargo_libs = libs.into_iter().collect();
+
+        self
+    }
+
+    /// Estimate the size of the packed `nupkg` in bytes.
+    ///
+    /// This is the size of the `nuspec` and libs, plus a small allowance for
+    /// the package's structural xml files. Compression is ignored.
+    pub fn estimated_size(&self) -> Result<u64, NugetPackError> {
+        let mut size = STRUCTURE_SIZE_ESTIMATE + self.spec.len() as u64;
+
+        for (target, lib) in self.cargo_libs.iter().filter(|&(target, _)| !target.is_unknown()) {
+            let metadata = fs::metadata(&lib.path).map_err(|e| lib_error(&target.rid(), &lib.path, e.into()))?;
+
+            size += metadata.len();
+        }
+
+        Ok(size)
+    }
+}
+
+/// A lib to pack for a target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NugetLib<'a> {
+    pub path: Cow<'a, Path>,
+    /// The rid to write the lib under instead of the target's.
+    ///
+    /// This is only set through `with_rid`, so it's always a valid rid.
+    rid: Option<String>,
+    /// The expected hex SHA-256 digest of the lib as it was built, before any stripping.
+    pub sha256: Option<String>,
+}
+
+impl<'a> NugetLib<'a> {
+    /// Get a lib to pack from its path.
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<Cow<'a, Path>>,
+    {
+        NugetLib {
+            path: path.into(),
+            rid: None,
+            sha256: None,
+        }
+    }
+
+    /// Write the lib under the given rid instead of its target's, like `ubuntu.22.04-x64`.
+    ///
+    /// The rid can only contain ASCII letters, digits, `.`, `-` and `_`.
+    pub fn with_rid(mut self, rid: &str) -> Result<Self, NugetPackError> {
+        if !is_rid(rid) {
+            Err(NugetPackError::InvalidRid { rid: rid.to_owned() })?;
+        }
+
+        self.rid = Some(rid.to_owned());
+
+        Ok(self)
+    }
+
+    /// Get the rid the lib is written under for a target.
+    pub fn resolved_rid(&self, target: &Target) -> Cow<'static, str> {
+        match self.rid {
+            Some(ref rid) => Cow::Owned(rid.clone()),
+            None => target.rid(),
+        }
+    }
+}
+
+/// Check whether a value looks like a rid, like `linux-x64` or `ubuntu.22.04-x64`.
+fn is_rid(rid: &str) -> bool {
+    !rid.is_empty() && !rid.starts_with('.')
+        && rid.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+}
+
+/// A map of targets to the libs to pack for them.
+///
+/// Libs can be collected from pairs of targets and paths.
+/// If a target is given more than once then the last path for it is used.
+/// Unknown targets are dropped with a warning because they can't be packed.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct NugetLibs<'a>(HashMap<Target, NugetLib<'a>>);
+
+impl<'a> From<HashMap<Target, NugetLib<'a>>> for NugetLibs<'a> {
+    fn from(libs: HashMap<Target, NugetLib<'a>>) -> Self {
+        NugetLibs(libs)
+    }
+}
+
+impl<'a> Deref for NugetLibs<'a> {
+    type Target = HashMap<Target, NugetLib<'a>>;
+
+    fn deref(&self) -> &HashMap<Target, NugetLib<'a>> {
+        &self.0
+    }
+}
+
+impl<'a> DerefMut for NugetLibs<'a> {
+    fn deref_mut(&mut self) -> &mut HashMap<Target, NugetLib<'a>> {
+        &mut self.0
+    }
+}
+
+impl<'a, P> FromIterator<(Target, P)> for NugetLibs<'a>
+where
+    P: Into<Cow<'a, Path>>,
+{
+    fn from_iter<I>(libs: I) -> Self
+    where
+        I: IntoIterator<Item = (Target, P)>,
+    {
+        let mut collected = HashMap::new();
+
+        for (target, path) in libs {
+            let lib = NugetLib::new(path);
+
+            if target.is_unknown() {
+                warn!("the lib '{}' has an unknown target and won't be packed", lib.path.display());
+                continue;
+            }
+
+            collected.insert(target, lib);
+        }
+
+        NugetLibs(collected)
+    }
+}
+
+/// A rough size in bytes of the relationships and content types files in a `nupkg`.
+const STRUCTURE_SIZE_ESTIMATE: u64 = 1024;
+
+/// Entries smaller than this many bytes are stored instead of deflated by default.
+pub const DEFAULT_COMPRESS_THRESHOLD: u64 = 512;
+
+/// The folder within `runtimes/{rid}` that libs are written to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NugetLibLayout<'a> {
+    /// Write libs to `runtimes/{rid}/native`.
+    Native,
+    /// Write libs to `runtimes/{rid}/lib/{tfm}`.
+    Lib { tfm: Cow<'a, str> },
+}
+
+impl<'a> Default for NugetLibLayout<'a> {
+    fn default() -> Self {
+        NugetLibLayout::Native
+    }
+}
+
+/// The method used to compress entries that are larger than the compress threshold.
+///
+/// NuGet clients only reliably support `Deflated` entries.
+/// Other methods are only useful for packages that are consumed by tools
+/// that can read them, and are only available when their crate feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NugetCompression {
+    Deflated,
+    /// Requires the `bzip2` feature.
+    Bzip2,
+}
+
+impl Default for NugetCompression {
+    fn default() -> Self {
+        NugetCompression::Deflated
+    }
 }
 
 /// A formatted `nupkg`.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Nupkg<'a> {
     pub name: Cow<'a, str>,
     pub rids: Vec<Cow<'a, str>>,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    /// The sizes of each entry, if they were recorded.
+    pub entry_stats: Option<Vec<EntryStat>>,
     pub buf: Buf,
 }
 
-fn options() -> FileOptions {
-    FileOptions::default().compression_method(CompressionMethod::Deflated)
+/// The size of an entry in a `nupkg` before and after compression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryStat {
+    pub name: String,
+    pub uncompressed_size: u64,
+    pub compressed_size: u64,
 }
 
-/// Pack a `nuspec` and native libs into a `nupkg`.
-pub fn pack<'a>(args: NugetPackArgs<'a>) -> Result<Nupkg, NugetPackError> {
-    let pkgs: Vec<_> = args.cargo_libs
-        .iter()
-        .filter_map(|(target, path)| {
-            if target.is_unknown() {
-                None
-            } else {
-                Some((target.rid(), path))
-            }
-        })
-        .collect();
+impl EntryStat {
+    /// The compressed size as a fraction of the uncompressed size.
+    ///
+    /// Entries that don't compress well have a ratio close to `1.0`.
+    pub fn ratio(&self) -> f64 {
+        match self.uncompressed_size {
+            0 => 1.0,
+            size => self.compressed_size as f64 / size as f64,
+        }
+    }
+}
 
-    if pkgs.len() == 0 {
-        Err(NugetPackError::NoValidTargets)?
+impl<'a> Nupkg<'a> {
+    /// Whether two packages have the same name and rids, regardless of their bytes.
+    pub fn meta_eq(&self, other: &Nupkg) -> bool {
+        self.name == other.name && self.rids == other.rids
     }
 
-    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    /// Get the bytes of the `nuspec` in the root of the package.
+    ///
+    /// The entry is found by its extension, so the package id doesn't need to be known.
+    pub fn nuspec_bytes(&self) -> Result<Vec<u8>, ZipError> {
+        let mut archive = ZipArchive::new(Cursor::new(&self.buf[..]))?;
 
-    let nuspec_path = {
-        let mut path = PathBuf::new();
-        path.set_file_name(args.id.as_ref());
-        path.set_extension("nuspec");
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let name = entry.name().replace("\\", "/");
 
-        path
-    };
+            if !name.contains('/') && name.ends_with(".nuspec") {
+                let mut buf = Vec::new();
+                entry.read_to_end(&mut buf)?;
+
+                return Ok(buf);
+            }
+        }
 
-    write_rels(&mut writer, &nuspec_path)?;
-    write_content_types(&mut writer)?;
+        Err(ZipError::FileNotFound)
+    }
+}
 
-    writer.start_file(nuspec_path.to_string_lossy(), options())?;
-    writer.write_all(&args.spec)?;
+/// Get the options for writing an entry of the given length.
+///
+/// Small entries are stored rather than deflated, because compressing them
+/// can produce larger output.
+fn options(len: u64, compress_threshold: u64, method: CompressionMethod) -> FileOptions {
+    let method = match len < compress_threshold {
+        true => CompressionMethod::Stored,
+        false => method,
+    };
 
-    for &(ref rid, ref lib_path) in &pkgs {
-        write_lib(&mut writer, &args.id, rid, lib_path).map_err(|e| {
-            NugetPackError::WriteLib {
-                rid: rid.to_string(),
-                lib_path: lib_path.to_string_lossy().into_owned(),
-                err: e,
-            }
-        })?;
+    FileOptions::default().compression_method(method)
+}
+
+/// Get the zip compression method for a package compression.
+///
+/// Methods that need a crate feature that isn't enabled are an error.
+fn compression_method(compression: NugetCompression) -> Result<CompressionMethod, NugetPackError> {
+    match compression {
+        NugetCompression::Deflated => Ok(CompressionMethod::Deflated),
+        #[cfg(feature = "bzip2")]
+        NugetCompression::Bzip2 => Ok(CompressionMethod::Bzip2),
+        #[cfg(not(feature = "bzip2"))]
+        NugetCompression::Bzip2 => Err(NugetPackError::UnsupportedCompression {
+            method: "bzip2",
+            feature: "bzip2",
+        }),
     }
+}
 
-    let buf = writer.finish()?.into_inner();
+/// A lib that's been read and is ready to be written into a package.
+struct Lib<'a> {
+    target: Target,
+    rid: Cow<'static, str>,
+    path: &'a Path,
+    buf: Vec<u8>,
+}
 
-    let rids = pkgs.into_iter().map(|(rid, _)| rid).collect();
-    let name = format!("{}.{}.nupkg", args.id, args.version);
+/// A header that's been read and is ready to be written into a package.
+struct Header<'a> {
+    name: Cow<'a, str>,
+    buf: Vec<u8>,
+}
 
-    Ok(Nupkg {
-        name: name.into(),
-        rids: rids,
-        buf: buf.into(),
-    })
+/// Pack a `nuspec` and native libs into a `nupkg`.
+///
+/// If `allow_empty` is set then a metadata-only package is produced when there are no libs.
+pub fn pack<'a>(args: NugetPackArgs<'a>) -> Result<Nupkg, NugetPackError> {
+    let method = compression_method(args.compression)?;
+    let compress_threshold = args.compress_threshold;
+
+    pack_with_entry_options(args, move |_, len| options(len, compress_threshold, method))
 }
 
-/// Write `/runtimes/{rid}/native/{lib}`.
-fn write_lib<W>(
-    writer: &mut ZipWriter<W>,
-    id: &str,
-    rid: &str,
-    lib_path: &Path,
-) -> Result<(), NugetWriteLibError>
+/// Get the options for writing an entry from its archive path and uncompressed length.
+pub type EntryOptions<'a> = &'a dyn Fn(&str, u64) -> FileOptions;
+
+/// Pack a `nuspec` and native libs into a `nupkg`, choosing the options for each entry.
+///
+/// The `compress_threshold` and `compression` args are ignored, so entries are
+/// written however `entry_options` says.
+pub fn pack_with_entry_options<'a, F>(args: NugetPackArgs<'a>, entry_options: F) -> Result<Nupkg<'static>, NugetPackError>
 where
-    W: Write + Seek,
+    F: Fn(&str, u64) -> FileOptions,
 {
-    let mut path = PathBuf::new();
-    path.push("runtimes");
-    path.push(rid);
-    path.push("native");
-    path.push(id);
+    pack_inner(args, &mut NoPackCache, &entry_options)
+}
 
-    if let Some(extension) = lib_path.extension() {
-        path.set_extension(extension);
-    }
+/// Pack a `nuspec` and native libs into a `nupkg`, reusing libs that have already been read.
+///
+/// Libs that haven't changed since they were cached aren't read or stripped again.
+pub fn pack_cached<'a>(args: NugetPackArgs<'a>, cache: &mut dyn PackCache) -> Result<Nupkg<'static>, NugetPackError> {
+    let method = compression_method(args.compression)?;
+    let compress_threshold = args.compress_threshold;
 
-    writer.start_file(path.to_string_lossy(), options())?;
+    pack_inner(args, cache, &|_, len| options(len, compress_threshold, method))
+}
 
-    let mut lib = File::open(lib_path)?;
-    copy(&mut lib, writer)?;
+fn pack_inner<'a>(
+    args: NugetPackArgs<'a>,
+    cache: &mut dyn PackCache,
+    entry_options: EntryOptions,
+) -> Result<Nupkg<'static>, NugetPackError> {
+    check_spec_encoding(&args.spec)?;
 
-    Ok(())
+    let libs = read_libs(&args, cache)?;
+    let headers = read_headers(&args)?;
+    let manifest = read_embedded_manifest(&args)?;
+
+    pack_libs(
+        &args,
+        &args.version,
+        &args.spec,
+        &libs,
+        &headers,
+        manifest.as_ref().map(|m| &m[..]),
+        entry_options,
+    )
 }
 
-/// Write `/_rels/.rels`.
-fn write_rels<W>(writer: &mut ZipWriter<W>, nuspec_path: &Path) -> Result<(), NugetPackError>
-where
-    W: Write + Seek,
-{
-    let (path, xml) = openxml::relationships(&nuspec_path)?;
+/// Pack a `nupkg` for each of the given versions.
+///
+/// Libs are only read once and shared between packages.
+/// The version in the `nuspec` is replaced for each package, so it needs
+/// to have been formatted for the version in `base`, otherwise it's an error.
+pub fn pack_variants<'a>(
+    base: NugetPackArgs<'a>,
+    versions: &[String],
+) -> Result<Vec<Nupkg<'static>>, NugetPackError> {
+    check_spec_encoding(&base.spec)?;
 
-    writer.start_file(path.to_string_lossy(), options())?;
-    writer.write_all(&xml)?;
+    let method = compression_method(base.compression)?;
+    let entry_options = |_: &str, len| options(len, base.compress_threshold, method);
 
-    Ok(())
+    let libs = read_libs(&base, &mut NoPackCache)?;
+    let headers = read_headers(&base)?;
+    let manifest = read_embedded_manifest(&base)?;
+
+    versions
+        .iter()
+        .map(|version| {
+            let spec = respec(&base.spec, &base.version, version)?;
+
+            pack_libs(
+                &base,
+                version,
+                &spec,
+                &libs,
+                &headers,
+                manifest.as_ref().map(|m| &m[..]),
+                &entry_options,
+            )
+        })
+        .collect()
 }
 
-/// Write `/[Content_Types].xml`.
-fn write_content_types<W>(writer: &mut ZipWriter<W>) -> Result<(), NugetPackError>
-where
-    W: Write + Seek,
-{
-    let (path, xml) = openxml::content_types()?;
+/// Check the `nuspec` is valid UTF-8, which is the encoding its xml declares.
+fn check_spec_encoding(spec: &[u8]) -> Result<(), NugetPackError> {
+    str::from_utf8(spec)
+        .map(|_| ())
+        .map_err(|e| NugetPackError::InvalidSpecEncoding { err: e })
+}
 
-    writer.start_file(path.to_string_lossy(), options())?;
-    writer.write_all(&xml)?;
+/// Read the libs for all known targets.
+///
+/// If `only` is set then libs for other targets are ignored.
+/// Libs are sorted by rid so they're written to the package in a stable order.
+/// Two libs can't be written under the same rid, including through rid overrides.
+fn read_libs<'a>(args: &'a NugetPackArgs, cache: &mut dyn PackCache) -> Result<Vec<Lib<'a>>, NugetPackError> {
+    let targets: Vec<_> = args.cargo_libs
+        .iter()
+        .filter(|&(target, _)| !target.is_unknown())
+        .filter(|&(target, _)| match args.only {
+            Some(ref only) => only.iter().any(|only| only.rid() == target.rid()),
+            None => true,
+        })
+        .collect();
 
-    Ok(())
+    if targets.len() == 0 && !args.allow_empty {
+        Err(NugetPackError::NoValidTargets)?
+    }
+
+    let mut libs = Vec::with_capacity(targets.len());
+    for (target, lib) in targets {
+        libs.push(read_lib(args.check_arch, args.strip, target, lib, cache)?);
+    }
+
+    libs.sort_by(|a, b| a.rid.cmp(&b.rid));
+
+    check_rid_collisions(&libs)?;
+
+    Ok(libs)
 }
 
-quick_error!{
-    #[derive(Debug)]
-    pub enum NugetPackError {
-        /// No valid platform targets were available
-        NoValidTargets {
-            display("No valid platform targets were supplied\nThis probably means you're running on an unsupported platform")
+/// Check no two libs are written under the same rid.
+///
+/// The libs must already be sorted by rid.
+fn check_rid_collisions(libs: &[Lib]) -> Result<(), NugetPackError> {
+    match libs.windows(2).find(|pair| pair[0].rid == pair[1].rid) {
+        Some(pair) => Err(NugetPackError::RidCollision {
+            rid: pair[1].rid.clone().into_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Read a lib into memory.
+///
+/// If `check_arch` is set then the lib's header must match the target's architecture.
+/// If `strip` is set then debug sections are removed from the lib where that's possible.
+/// Libs are read from the cache if they haven't changed since they were added to it.
+/// The lib's rid is used instead of the target's if it has one.
+/// If the lib has an expected digest then the bytes read must match it, before they're stripped.
+/// Libs with an expected digest aren't read from the cache, which only keeps stripped bytes.
+fn read_lib<'a>(
+    check_arch: bool,
+    strip: bool,
+    target: &Target,
+    lib: &'a NugetLib,
+    cache: &mut dyn PackCache,
+) -> Result<Lib<'a>, NugetPackError> {
+    let path = &*lib.path;
+    let rid = lib.resolved_rid(target);
+
+    let read = || -> Result<Vec<u8>, NugetWriteLibError> {
+        let mut buf = Vec::new();
+        File::open(path)?.read_to_end(&mut buf)?;
+
+        if let Some(ref expected) = lib.sha256 {
+            let actual = sha256_hex(&buf);
+
+            if !expected.eq_ignore_ascii_case(&actual) {
+                Err(NugetWriteLibError::DigestMismatch {
+                    expected: expected.to_owned(),
+                    actual: actual,
+                })?;
+            }
         }
-        /// A zip writing error.
-        Zip(err: ZipError) {
-            display("Error building nupkg\nCaused by: {}", err)
-            from()
+
+        if check_arch {
+            self::check_arch(target, &buf)?;
         }
-        /// A general io error.
-        Io(err: IoError) {
-            display("Error building nupkg\nCaused by: {}", err)
-            from()
+
+        if strip {
+            let name = path.file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_else(|| rid.clone());
+
+            if let Some(stripped) = strip::strip_debug(target, &name, &buf) {
+                return Ok(stripped);
+            }
         }
-        /// An xml formatting error.
-        Xml(err: xml::Error) {
-            display("Error building nupkg\nCaused by: {}", err)
-            from()
+
+        Ok(buf)
+    };
+
+    let key = match lib.sha256 {
+        Some(_) => None,
+        None => PackCacheKey::for_lib(path, strip),
+    };
+
+    let buf = match key.as_ref().and_then(|key| cache.get(key)) {
+        Some(buf) => {
+            if check_arch {
+                self::check_arch(target, &buf).map_err(|e| lib_error(&rid, path, e))?;
+            }
+
+            buf
         }
-        /// An error with a specific library.
-        WriteLib { rid: String, lib_path: String, err: NugetWriteLibError } {
-            display("Error reading lib {} at path {}\nCaused by: {}", rid, lib_path, err)
+        None => {
+            let buf = read().map_err(|e| lib_error(&rid, path, e))?;
+
+            if let Some(key) = key {
+                cache.insert(key, &buf);
+            }
+
+            buf
         }
-    }
+    };
+
+    Ok(Lib {
+        target: *target,
+        rid: rid,
+        path: path,
+        buf: buf,
+    })
 }
 
-quick_error!{
-    #[derive(Debug)]
-    pub enum NugetWriteLibError {
-        /// A zip writing error.
-        Zip(err: ZipError) {
-            display("Error reading lib\nCaused by: {}", err)
-            from()
-        }
-        /// A general io error.
-        Io(err: IoError) {
-            display("Error reading lib\nCaused by: {}", err)
-            from()
-        }
-        /// An error parsing a library path.
-        BadPath { path: String } {
-            display("Error parsing path '{}'", path)
+/// Read the `Cargo.toml` to embed in the package, if there is one.
+fn read_embedded_manifest(args: &NugetPackArgs) -> Result<Option<Vec<u8>>, NugetPackError> {
+    let path = match args.embed_manifest {
+        Some(ref path) => path,
+        None => return Ok(None),
+    };
+
+    let mut buf = Vec::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_end(&mut buf))
+        .map_err(|e| NugetPackError::EmbedManifest {
+            manifest_path: path.to_string_lossy().into_owned(),
+            err: e,
+        })?;
+
+    Ok(Some(buf))
+}
+
+/// Read the headers to bundle alongside libs.
+///
+/// Headers are written to the package in the order they're given.
+/// Headers are written by their file name, so two headers can't share one.
+fn read_headers<'a>(args: &'a NugetPackArgs) -> Result<Vec<Header<'a>>, NugetPackError> {
+    let headers = args.headers
+        .iter()
+        .map(|path| {
+            let read = || -> Result<Header<'a>, NugetWriteLibError> {
+                let name = path.file_name()
+                    .map(|name| name.to_string_lossy())
+                    .ok_or_else(|| NugetWriteLibError::BadPath {
+                        path: path.to_string_lossy().into_owned(),
+                    })?;
+
+                path_component(&name)?;
+
+                let mut buf = Vec::new();
+                File::open(path)?.read_to_end(&mut buf)?;
+
+                Ok(Header {
+                    name: name,
+                    buf: buf,
+                })
+            };
+
+            read().map_err(|e| header_error(path, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (i, header) in headers.iter().enumerate() {
+        if headers[..i].iter().any(|other| other.name == header.name) {
+            return Err(NugetPackError::DuplicateHeader {
+                name: header.name.clone().into_owned(),
+            });
         }
     }
+
+    Ok(headers)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::path::PathBuf;
-    use std::collections::HashMap;
-    use super::*;
+/// Pack a `nuspec` and libs that have already been read into a `nupkg`.
+fn pack_libs(
+    args: &NugetPackArgs,
+    version: &str,
+    spec: &[u8],
+    libs: &[Lib],
+    headers: &[Header],
+    manifest: Option<&[u8]>,
+    entry_options: EntryOptions,
+) -> Result<Nupkg<'static>, NugetPackError> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
 
-    macro_rules! assert_inavlid {
-        ($args:ident, $err:pat) => ({
-            let nuspec = pack($args);
+    let nuspec_path = PathBuf::from(nuspec_entry_name(&args.id));
 
-            match nuspec {
-                Err($err) => (),
-                r => panic!("{:?}", r)
-            }
-        })
+    let mut uncompressed_size = 0;
+
+    let (core_properties_path, core_properties) =
+        openxml::core_properties(&args.id, version, &args.authors, &args.description)?;
+
+    let mut content_types = lib_content_types(libs);
+
+    if manifest.is_some() {
+        content_types.push(("orig".to_owned(), "text/plain"));
     }
 
-    #[test]
-    fn pack_with_no_targets() {
-        let args = NugetPackArgs {
-            id: "some_pkg".into(),
-            version: "0.1.1".into(),
-            spec: &vec![].into(),
-            cargo_libs: HashMap::new(),
-        };
+    uncompressed_size += write_content_types(&mut writer, &content_types, entry_options)?;
+    uncompressed_size += write_rels(
+        &mut writer,
+        &nuspec_path,
+        &core_properties_path,
+        entry_options,
+    )?;
 
-        assert_inavlid!(args, NugetPackError::NoValidTargets);
+    start_file(&mut writer, &nuspec_path, spec.len() as u64, entry_options)?;
+    writer.write_all(spec)?;
+    uncompressed_size += spec.len() as u64;
+
+    for lib in libs {
+        write_lib(&mut writer, args, lib, entry_options).map_err(|e| lib_error(&lib.rid, lib.path, e))?;
+        uncompressed_size += lib.buf.len() as u64;
     }
 
-    #[test]
-    fn pack_with_unknown_target() {
-        let mut targets = HashMap::new();
-        targets.insert(Target::Unknown, PathBuf::new().into());
+    if args.runtime_json {
+        let graph = runtime_json(libs);
 
-        let args = NugetPackArgs {
-            id: "some_pkg".into(),
-            version: "0.1.1".into(),
-            spec: &vec![].into(),
-            cargo_libs: targets,
-        };
+        start_file(&mut writer, Path::new(RUNTIME_JSON_PATH), graph.len() as u64, entry_options)?;
+        writer.write_all(graph.as_bytes())?;
+        uncompressed_size += graph.len() as u64;
+    }
 
-        assert_inavlid!(args, NugetPackError::NoValidTargets);
+    for header in headers {
+        let name = format!("build/native/include/{}", header.name);
+
+        start_entry(&mut writer, &name, header.buf.len() as u64, entry_options)?;
+        writer.write_all(&header.buf)?;
+        uncompressed_size += header.buf.len() as u64;
+    }
+
+    if let Some(manifest) = manifest {
+        start_file(&mut writer, Path::new(EMBEDDED_MANIFEST_PATH), manifest.len() as u64, entry_options)?;
+        writer.write_all(manifest)?;
+        uncompressed_size += manifest.len() as u64;
+    }
+
+    start_file(&mut writer, &core_properties_path, core_properties.len() as u64, entry_options)?;
+    writer.write_all(&core_properties)?;
+    uncompressed_size += core_properties.len() as u64;
+
+    let buf = writer.finish()?.into_inner();
+
+    let entry_stats = match args.entry_stats {
+        true => Some(entry_stats(&buf)?),
+        false => None,
+    };
+
+    let rids = libs.iter().map(|lib| lib.rid.clone()).collect();
+    let name = format!("{}.{}.nupkg", args.id, version);
+
+    Ok(Nupkg {
+        name: name.into(),
+        rids: rids,
+        compressed_size: buf.len() as u64,
+        uncompressed_size: uncompressed_size,
+        entry_stats: entry_stats,
+        buf: buf.into(),
+    })
+}
+
+/// Merge runtime libs into an existing `nupkg`.
+///
+/// New libs are written with the same layout as the base package's libs, under their rid
+/// or its override. New libs are read like they are for `pack`, so their expected digests
+/// are checked, and `check_arch` and `strip` work the same way.
+/// A lib for a rid that's already in the base package, or two new libs for the same rid, is an error.
+/// Other entries are copied from the base package as-is, except `[Content_Types].xml`,
+/// which is rewritten to cover the new libs. An existing `runtime.json` isn't updated.
+pub fn merge_runtimes<'a>(
+    base: &Buf,
+    new_libs: HashMap<Target, NugetLib<'a>>,
+    check_arch: bool,
+    strip: bool,
+) -> Result<Nupkg<'static>, NugetPackError> {
+    let mut archive = ZipArchive::new(Cursor::new(&base[..]))?;
+
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+
+        let mut buf = Vec::new();
+        entry.read_to_end(&mut buf)?;
+
+        entries.push((entry.name().replace("\\", "/"), entry.compression(), buf));
+    }
+
+    let (id, version) = {
+        let &(ref name, _, ref spec) = entries
+            .iter()
+            .find(|&&(ref name, _, _)| !name.contains('/') && name.ends_with(".nuspec"))
+            .ok_or(NugetPackError::InvalidBase {
+                reason: "it doesn't contain a nuspec",
+            })?;
+
+        let version = str::from_utf8(spec)
+            .ok()
+            .and_then(nuspec_version)
+            .ok_or(NugetPackError::InvalidBase {
+                reason: "its nuspec doesn't contain a version",
+            })?;
+
+        (name.trim_end_matches(".nuspec").to_owned(), version.to_owned())
+    };
+
+    let base_rids: Vec<_> = entries
+        .iter()
+        .filter_map(|&(ref name, _, _)| runtime_entry_rid(name))
+        .collect();
+
+    let layout = entries
+        .iter()
+        .filter_map(|&(ref name, _, _)| runtime_entry_layout(name))
+        .next()
+        .unwrap_or_default();
+
+    let mut libs = Vec::with_capacity(new_libs.len());
+    for (target, lib) in new_libs.iter().filter(|&(target, _)| !target.is_unknown()) {
+        let rid = lib.resolved_rid(target);
+
+        if base_rids.contains(&&*rid) {
+            return Err(NugetPackError::RidCollision { rid: rid.into_owned() });
+        }
+
+        libs.push(read_lib(check_arch, strip, target, lib, &mut NoPackCache)?);
+    }
+
+    libs.sort_by(|a, b| a.rid.cmp(&b.rid));
+
+    check_rid_collisions(&libs)?;
+
+    let entry_options: EntryOptions =
+        &|_, len| options(len, DEFAULT_COMPRESS_THRESHOLD, CompressionMethod::Deflated);
+
+    let (content_types_path, _) = openxml::content_types(&[])?;
+    let content_types_name = content_types_path.to_string_lossy().into_owned();
+
+    let mut content_types = lib_content_types(&libs);
+    for &(ref name, _, ref buf) in &entries {
+        if runtime_entry_rid(name).is_some() {
+            if let Some(extension) = Path::new(name).extension() {
+                let extension = extension.to_string_lossy().into_owned();
+                let content_type = openxml::detect_content_type(buf, Some(&extension));
+
+                content_types.push((extension, content_type));
+            }
+        }
+
+        if name == EMBEDDED_MANIFEST_PATH {
+            content_types.push(("orig".to_owned(), "text/plain"));
+        }
+    }
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    let mut uncompressed_size = write_content_types(&mut writer, &content_types, entry_options)?;
+
+    for &(ref name, method, ref buf) in entries.iter().filter(|&&(ref name, _, _)| *name != content_types_name) {
+        writer.start_file(name.as_str(), FileOptions::default().compression_method(method))?;
+        writer.write_all(buf)?;
+        uncompressed_size += buf.len() as u64;
+    }
+
+    for lib in &libs {
+        let extension = lib_extension(&lib.target, lib.path);
+        let name = lib_entry_name(&id, &layout, &lib.rid, extension.as_ref().map(|e| &**e))
+            .map_err(|e| lib_error(&lib.rid, lib.path, e))?;
+
+        start_entry(&mut writer, &name, lib.buf.len() as u64, entry_options)?;
+        writer.write_all(&lib.buf)?;
+        uncompressed_size += lib.buf.len() as u64;
+    }
+
+    let buf = writer.finish()?.into_inner();
+
+    let mut rids: Vec<Cow<str>> = base_rids
+        .iter()
+        .map(|&rid| Cow::Owned(rid.to_owned()))
+        .chain(libs.iter().map(|lib| Cow::Owned(lib.rid.to_string())))
+        .collect();
+    rids.sort();
+    rids.dedup();
+
+    Ok(Nupkg {
+        name: format!("{}.{}.nupkg", id, version).into(),
+        rids: rids,
+        compressed_size: buf.len() as u64,
+        uncompressed_size: uncompressed_size,
+        entry_stats: None,
+        buf: buf.into(),
+    })
+}
+
+/// Read the size of each entry in a packed `nupkg`.
+fn entry_stats(buf: &[u8]) -> Result<Vec<EntryStat>, ZipError> {
+    let mut archive = ZipArchive::new(Cursor::new(buf))?;
+
+    let mut stats = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+
+        stats.push(EntryStat {
+            name: entry.name().replace("\\", "/"),
+            uncompressed_size: entry.size(),
+            compressed_size: entry.compressed_size(),
+        });
+    }
+
+    Ok(stats)
+}
+
+/// Get the rid of an entry under `runtimes/{rid}`.
+fn runtime_entry_rid(name: &str) -> Option<&str> {
+    let mut parts = name.split('/');
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("runtimes"), Some(rid), Some(_)) => Some(rid),
+        _ => None,
+    }
+}
+
+/// Get the layout of an entry under `runtimes/{rid}`.
+fn runtime_entry_layout(name: &str) -> Option<NugetLibLayout<'static>> {
+    let mut parts = name.split('/').skip(2);
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("native"), Some(_), None) => Some(NugetLibLayout::Native),
+        (Some("lib"), Some(tfm), Some(_)) => Some(NugetLibLayout::Lib {
+            tfm: Cow::Owned(tfm.to_owned()),
+        }),
+        _ => None,
+    }
+}
+
+/// Get the version from a `nuspec` formatted by `nuget::spec`.
+fn nuspec_version(xml: &str) -> Option<&str> {
+    let start = xml.find("<version>")? + "<version>".len();
+    let len = xml[start..].find("</version>")?;
+
+    Some(xml[start..start + len].trim())
+}
+
+/// The path an embedded `Cargo.toml` is written to.
+pub const EMBEDDED_MANIFEST_PATH: &'static str = "build/Cargo.toml.orig";
+
+/// The path of the runtime graph in the root of a package.
+pub const RUNTIME_JSON_PATH: &'static str = "runtime.json";
+
+/// Format a runtime graph that imports the fallbacks for each packed rid.
+///
+/// Rids are sorted so the output is stable, and escaped because they can come from config.
+fn runtime_json(libs: &[Lib]) -> String {
+    let mut rids: Vec<_> = libs.iter().map(|lib| (&*lib.rid, lib.target.rid_graph_fallbacks())).collect();
+    rids.sort_by(|a, b| a.0.cmp(b.0));
+    rids.dedup_by(|a, b| a.0 == b.0);
+
+    let runtimes: Vec<_> = rids
+        .iter()
+        .map(|&(rid, ref fallbacks)| {
+            let imports: Vec<_> = fallbacks.iter().map(|rid| json_string(rid)).collect();
+
+            format!("    {}: {{ \"#import\": [{}] }}", json_string(rid), imports.join(", "))
+        })
+        .collect();
+
+    format!("{{\n  \"runtimes\": {{\n{}\n  }}\n}}\n", runtimes.join(",\n"))
+}
+
+/// Format a value as a quoted JSON string.
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+/// Get the name of the `nuspec` entry in the root of a package.
+///
+/// Ids can contain dots, like `Some.Pkg`, so the extension is appended rather than replaced.
+pub fn nuspec_entry_name(id: &str) -> String {
+    format!("{}.nuspec", id)
+}
+
+/// Get the name of the entry a runtime lib is written to, like `runtimes/linux-x64/native/some_pkg.so`.
+///
+/// The rid, tfm and id must each be a single path component.
+/// The name is always joined with `/`, regardless of the host's path separator.
+pub fn lib_entry_name(
+    id: &str,
+    layout: &NugetLibLayout,
+    rid: &str,
+    extension: Option<&str>,
+) -> Result<String, NugetWriteLibError> {
+    let folder = match *layout {
+        NugetLibLayout::Native => Cow::Borrowed("native"),
+        NugetLibLayout::Lib { ref tfm } => Cow::Owned(format!("lib/{}", path_component(tfm)?)),
+    };
+
+    let mut name = format!("runtimes/{}/{}/{}", path_component(rid)?, folder, path_component(id)?);
+
+    if let Some(extension) = extension {
+        name.push('.');
+        name.push_str(extension);
+    }
+
+    Ok(name)
+}
+
+/// Start writing an entry with the options for its path and length.
+fn start_file<W>(
+    writer: &mut ZipWriter<W>,
+    path: &Path,
+    len: u64,
+    entry_options: EntryOptions,
+) -> Result<(), ZipError>
+where
+    W: Write + Seek,
+{
+    start_entry(writer, &path.to_string_lossy(), len, entry_options)
+}
+
+/// Start writing an entry with the options for its name and length.
+fn start_entry<W>(
+    writer: &mut ZipWriter<W>,
+    name: &str,
+    len: u64,
+    entry_options: EntryOptions,
+) -> Result<(), ZipError>
+where
+    W: Write + Seek,
+{
+    let options = entry_options(name, len);
+
+    writer.start_file(name, options)
+}
+
+/// Replace the version in a `nuspec` formatted by `nuget::spec`.
+///
+/// A `nuspec` that doesn't contain the `from` version is an error, because the
+/// package's name and its `nuspec` would disagree about its version.
+fn respec(spec: &[u8], from: &str, to: &str) -> Result<Vec<u8>, NugetPackError> {
+    let xml = str::from_utf8(spec).map_err(|e| NugetPackError::InvalidSpecEncoding { err: e })?;
+
+    let from = format!("<version>{}</version>", from);
+    let to = format!("<version>{}</version>", to);
+
+    if !xml.contains(&from) {
+        return Err(NugetPackError::SpecVersionMismatch { version: from });
+    }
+
+    Ok(xml.replacen(&from, &to, 1).into_bytes())
+}
+
+fn lib_error(rid: &str, path: &Path, err: NugetWriteLibError) -> NugetPackError {
+    NugetPackError::WriteLib {
+        rid: rid.to_owned(),
+        lib_path: path.to_string_lossy().into_owned(),
+        err: err,
+    }
+}
+
+fn header_error(path: &Path, err: NugetWriteLibError) -> NugetPackError {
+    NugetPackError::WriteHeader {
+        header_path: path.to_string_lossy().into_owned(),
+        err: err,
+    }
+}
+
+/// Write `/runtimes/{rid}/native/{lib}` or `/runtimes/{rid}/lib/{tfm}/{lib}`.
+///
+/// See `lib_entry_name` for how the entry is named.
+fn write_lib<W>(
+    writer: &mut ZipWriter<W>,
+    args: &NugetPackArgs,
+    lib: &Lib,
+    entry_options: EntryOptions,
+) -> Result<(), NugetWriteLibError>
+where
+    W: Write + Seek,
+{
+    let extension = lib_extension(&lib.target, lib.path);
+    let name = lib_entry_name(&args.id, &args.layout, &lib.rid, extension.as_ref().map(|e| &**e))?;
+
+    start_entry(writer, &name, lib.buf.len() as u64, entry_options)?;
+    writer.write_all(&lib.buf)?;
+
+    Ok(())
+}
+
+/// Get the extension for a lib from its path, or from its target if the path doesn't have one.
+pub fn lib_extension<'a>(target: &Target, path: &'a Path) -> Option<Cow<'a, str>> {
+    match path.extension() {
+        Some(extension) => Some(extension.to_string_lossy()),
+        None => target.extension().map(Cow::Borrowed),
+    }
+}
+
+/// Check a value is safe to use as a single component of an archive path.
+///
+/// Values that could traverse outside of their folder, like `..` or `/evil`, are rejected.
+fn path_component(component: &str) -> Result<&str, NugetWriteLibError> {
+    let is_separator = |c: char| c == '/' || c == '\\' || c == ':';
+
+    match component {
+        "" | "." | ".." => Err(NugetWriteLibError::BadPath {
+            path: component.to_owned(),
+        }),
+        _ if component.contains(is_separator) => Err(NugetWriteLibError::BadPath {
+            path: component.to_owned(),
+        }),
+        _ => Ok(component),
+    }
+}
+
+/// Check the architecture in a lib's header matches the target.
+///
+/// Libs with unrecognised headers are assumed to match.
+fn check_arch(target: &Target, lib: &[u8]) -> Result<(), NugetWriteLibError> {
+    let expected = target.cross().map(|target| target.arch());
+    let actual = binary::arch(lib);
+
+    match (expected, actual) {
+        (Some(expected), Some(actual)) if expected != actual => {
+            Err(NugetWriteLibError::ArchMismatch {
+                expected: expected,
+                actual: actual,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Write `/_rels/.rels`, returning its uncompressed size.
+fn write_rels<W>(
+    writer: &mut ZipWriter<W>,
+    nuspec_path: &Path,
+    core_properties_path: &Path,
+    entry_options: EntryOptions,
+) -> Result<u64, NugetPackError>
+where
+    W: Write + Seek,
+{
+    let (path, xml) = openxml::relationships(nuspec_path, core_properties_path)?;
+
+    start_file(writer, &path, xml.len() as u64, entry_options)?;
+    writer.write_all(&xml)?;
+
+    Ok(xml.len() as u64)
+}
+
+/// Get the content types for lib extensions, detected from the lib's contents.
+fn lib_content_types(libs: &[Lib]) -> Vec<(String, &'static str)> {
+    libs.iter()
+        .filter_map(|lib| {
+            lib_extension(&lib.target, lib.path).map(|extension| {
+                let content_type = openxml::detect_content_type(&lib.buf, Some(&*extension));
+
+                (extension.into_owned(), content_type)
+            })
+        })
+        .collect()
+}
+
+/// Write `/[Content_Types].xml`, returning its uncompressed size.
+///
+/// Extensions that aren't known are registered with the given content types.
+fn write_content_types<W>(
+    writer: &mut ZipWriter<W>,
+    extra: &[(String, &'static str)],
+    entry_options: EntryOptions,
+) -> Result<u64, NugetPackError>
+where
+    W: Write + Seek,
+{
+    let (path, xml) = openxml::content_types(extra)?;
+
+    start_file(writer, &path, xml.len() as u64, entry_options)?;
+    writer.write_all(&xml)?;
+
+    Ok(xml.len() as u64)
+}
+
+quick_error!{
+    #[derive(Debug)]
+    pub enum NugetPackError {
+        /// No valid platform targets were available
+        NoValidTargets {
+            display("No valid platform targets were supplied\nThis probably means you're running on an unsupported platform")
+        }
+        /// A zip writing error.
+        Zip(err: ZipError) {
+            source(err)
+            display("Error building nupkg\nCaused by: {}", err)
+            from()
+        }
+        /// A general io error.
+        Io(err: IoError) {
+            source(err)
+            display("Error building nupkg\nCaused by: {}", err)
+            from()
+        }
+        /// An xml formatting error.
+        Xml(err: xml::Error) {
+            source(err)
+            display("Error building nupkg\nCaused by: {}", err)
+            from()
+        }
+        /// An error with a specific library.
+        WriteLib { rid: String, lib_path: String, err: NugetWriteLibError } {
+            source(err)
+            display("Error reading lib {} at path {}\nCaused by: {}", rid, lib_path, err)
+        }
+        /// An error with a specific header.
+        WriteHeader { header_path: String, err: NugetWriteLibError } {
+            source(err)
+            display("Error reading header at path {}\nCaused by: {}", header_path, err)
+        }
+        /// Two headers have the same file name.
+        DuplicateHeader { name: String } {
+            display("More than one header is named '{}'", name)
+        }
+        /// An error reading the manifest to embed.
+        EmbedManifest { manifest_path: String, err: IoError } {
+            source(err)
+            display("Error reading the manifest to embed at path {}\nCaused by: {}", manifest_path, err)
+        }
+        /// The nuspec isn't valid UTF-8.
+        InvalidSpecEncoding { err: Utf8Error } {
+            source(err)
+            display("The nuspec isn't valid UTF-8\nCaused by: {}", err)
+        }
+        /// A compression method was requested without its crate feature enabled.
+        UnsupportedCompression { method: &'static str, feature: &'static str } {
+            display("The '{}' compression method requires the '{}' feature to be enabled", method, feature)
+        }
+        /// The nuspec doesn't contain the version it's expected to.
+        SpecVersionMismatch { version: String } {
+            display("The nuspec doesn't contain the expected version element '{}'", version)
+        }
+        /// A lib's rid override isn't a valid rid.
+        InvalidRid { rid: String } {
+            display("The rid '{}' isn't valid, expected letters, digits, '.', '-' and '_', like 'ubuntu.22.04-x64'", rid)
+        }
+        /// More than one lib would be written under the same rid.
+        ///
+        /// This includes a lib being merged into a nupkg that already has its rid.
+        RidCollision { rid: String } {
+            display("More than one lib would be written under the rid '{}'", rid)
+        }
+        /// A nupkg to merge libs into can't be read.
+        InvalidBase { reason: &'static str } {
+            display("The nupkg can't be merged into because {}", reason)
+        }
+        /// An entry in a nupkg has a path that could escape the folder it's extracted to.
+        UnsafeEntry { name: String } {
+            display("The nupkg entry '{}' can't be safely extracted", name)
+        }
+    }
+}
+
+quick_error!{
+    #[derive(Debug)]
+    pub enum NugetWriteLibError {
+        /// A zip writing error.
+        Zip(err: ZipError) {
+            source(err)
+            display("Error reading lib\nCaused by: {}", err)
+            from()
+        }
+        /// A general io error.
+        Io(err: IoError) {
+            source(err)
+            display("Error reading lib\nCaused by: {}", err)
+            from()
+        }
+        /// An error parsing a library path.
+        BadPath { path: String } {
+            display("Error parsing path '{}'", path)
+        }
+        /// The library doesn't match its expected digest.
+        DigestMismatch { expected: String, actual: String } {
+            display("The lib's SHA-256 digest is '{}' but '{}' was expected", actual, expected)
+        }
+        /// The library was built for a different architecture than its target.
+        ArchMismatch { expected: Arch, actual: Arch } {
+            display("The lib was built for '{}' but its target expects '{}'", actual.rid(), expected.rid())
+        }
+    }
+}
+
+impl NugetPackError {
+    /// A stable, machine-readable code for this error.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            NugetPackError::NoValidTargets => "E_NUPKG_NO_TARGETS",
+            NugetPackError::Zip(_) => "E_NUPKG_ZIP",
+            NugetPackError::Io(_) => "E_NUPKG_IO",
+            NugetPackError::Xml(_) => "E_NUPKG_XML",
+            NugetPackError::WriteLib { .. } => "E_NUPKG_WRITE_LIB",
+            NugetPackError::WriteHeader { .. } => "E_NUPKG_WRITE_HEADER",
+            NugetPackError::DuplicateHeader { .. } => "E_NUPKG_DUPLICATE_HEADER",
+            NugetPackError::EmbedManifest { .. } => "E_NUPKG_EMBED_MANIFEST",
+            NugetPackError::UnsafeEntry { .. } => "E_NUPKG_UNSAFE_ENTRY",
+            NugetPackError::InvalidRid { .. } => "E_NUPKG_INVALID_RID",
+            NugetPackError::SpecVersionMismatch { .. } => "E_NUPKG_SPEC_VERSION",
+            NugetPackError::RidCollision { .. } => "E_NUPKG_RID_COLLISION",
+            NugetPackError::InvalidBase { .. } => "E_NUPKG_INVALID_BASE",
+            NugetPackError::UnsupportedCompression { .. } => "E_NUPKG_UNSUPPORTED_COMPRESSION",
+            NugetPackError::InvalidSpecEncoding { .. } => "E_NUPKG_SPEC_ENCODING",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use zip::ZipArchive;
+    use args::{Arch, CrossTarget};
+    use nuget::MemoryPackCache;
+    use tempdir::TempDir;
+    use super::*;
+
+    /// Get the names of entries in the archive, with separators normalized.
+    fn entry_names(buf: &[u8]) -> Vec<String> {
+        let mut archive = ZipArchive::new(Cursor::new(buf)).unwrap();
+
+        (0..archive.len())
+            .map(|i| archive.by_index(i).unwrap().name().replace("\\", "/"))
+            .collect()
+    }
+
+    fn args<'a>(spec: &'a Buf) -> NugetPackArgs<'a> {
+        NugetPackArgs::new("some_pkg", "0.1.1", "Someone", "A description for this package", spec)
+    }
+
+    /// Get the archive path of the core properties part for a package.
+    fn core_properties_path(id: &str, version: &str) -> String {
+        openxml::core_properties(id, version, "", "")
+            .unwrap()
+            .0
+            .to_string_lossy()
+            .replace("\\", "/")
+    }
+
+    /// Get the compression method for the entry with the given name.
+    fn entry_compression(buf: &[u8], name: &str) -> CompressionMethod {
+        let mut archive = ZipArchive::new(Cursor::new(buf)).unwrap();
+
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i).unwrap();
+
+            if entry.name().replace("\\", "/") == name {
+                return entry.compression();
+            }
+        }
+
+        panic!("missing entry {}", name)
+    }
+
+    fn linux_x64_lib<'a>(path: &'a str) -> NugetLibs<'a> {
+        let mut targets = NugetLibs::default();
+        targets.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new(path)),
+        );
+
+        targets
+    }
+
+    macro_rules! assert_inavlid {
+        ($args:ident, $err:pat) => ({
+            let nuspec = pack($args);
+
+            match nuspec {
+                Err($err) => (),
+                r => panic!("{:?}", r)
+            }
+        })
+    }
+
+    #[test]
+    fn pack_with_no_targets() {
+        let spec = vec![].into();
+        let args = args(&spec);
+
+        assert_inavlid!(args, NugetPackError::NoValidTargets);
+    }
+
+    #[test]
+    fn pack_with_unknown_target() {
+        let mut targets = NugetLibs::default();
+        targets.insert(Target::Unknown, NugetLib::new(PathBuf::new()));
+
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: targets,
+            ..args(&spec)
+        };
+
+        assert_inavlid!(args, NugetPackError::NoValidTargets);
+    }
+
+    #[test]
+    fn collect_libs_from_iter() {
+        let libs: NugetLibs = vec![
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("first.so")),
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("native.dll")),
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("second.so")),
+            (Target::Unknown, Path::new("native.a")),
+        ].into_iter().collect();
+
+        assert_eq!(2, libs.len());
+        assert_eq!(
+            Path::new("second.so"),
+            &*libs[&Target::Cross(CrossTarget::Linux(Arch::x64))].path
+        );
+        assert_eq!(
+            Path::new("native.dll"),
+            &*libs[&Target::Cross(CrossTarget::Windows(Arch::x64))].path
+        );
+    }
+
+    #[test]
+    fn pack_with_expected_digest() {
+        let mut source = Vec::new();
+        File::open("tests/native/Cargo.toml").unwrap().read_to_end(&mut source).unwrap();
+
+        let actual = sha256_hex(&source);
+
+        let pack_with_digest = |sha256: &str| {
+            let mut targets = NugetLibs::default();
+            targets.insert(
+                Target::Cross(CrossTarget::Linux(Arch::x64)),
+                NugetLib {
+                    sha256: Some(sha256.to_owned()),
+                    ..NugetLib::new(Path::new("tests/native/Cargo.toml"))
+                },
+            );
+
+            let spec = vec![].into();
+            let args = NugetPackArgs {
+                cargo_libs: targets,
+                ..args(&spec)
+            };
+
+            pack(args).map(|_| ())
+        };
+
+        pack_with_digest(&actual).unwrap();
+        pack_with_digest(&actual.to_uppercase()).unwrap();
+
+        let expected = "0".repeat(64);
+        match pack_with_digest(&expected) {
+            Err(NugetPackError::WriteLib {
+                err: NugetWriteLibError::DigestMismatch { expected: ref e, actual: ref a },
+                ..
+            }) if *e == expected && *a == actual => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn pack_with_rid_override() {
+        let mut targets = NugetLibs::default();
+        targets.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")).with_rid("ubuntu.22.04-x64").unwrap(),
+        );
+
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: targets,
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert_eq!(vec!["ubuntu.22.04-x64"], nupkg.rids);
+        assert!(entry_names(&nupkg.buf).contains(&"runtimes/ubuntu.22.04-x64/native/some_pkg.toml".to_owned()));
+    }
+
+    #[test]
+    fn pack_with_colliding_rid_overrides() {
+        let mut targets = NugetLibs::default();
+        targets.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")).with_rid("ubuntu.22.04-x64").unwrap(),
+        );
+        targets.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x86)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")).with_rid("ubuntu.22.04-x64").unwrap(),
+        );
+
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: targets,
+            ..args(&spec)
+        };
+
+        match pack(args) {
+            Err(NugetPackError::RidCollision { ref rid }) if rid == "ubuntu.22.04-x64" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn pack_with_rid_override_colliding_with_target() {
+        let mut targets = NugetLibs::default();
+        targets.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")),
+        );
+        targets.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x86)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")).with_rid("linux-x64").unwrap(),
+        );
+
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: targets,
+            ..args(&spec)
+        };
+
+        match pack(args) {
+            Err(NugetPackError::RidCollision { ref rid }) if rid == "linux-x64" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn lib_with_invalid_rid() {
+        for &rid in &["", "../evil", ".hidden", "linux\"x64", "linux/x64"] {
+            match NugetLib::new(Path::new("tests/native/Cargo.toml")).with_rid(rid) {
+                Err(NugetPackError::InvalidRid { rid: ref r }) if r == rid => (),
+                r => panic!("{:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn pack_empty_metadata_package() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            allow_empty: true,
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        let mut entries = entry_names(&nupkg.buf);
+        entries.sort();
+
+        // The core properties are referenced from `_rels/.rels`, so they're written even without libs
+        let mut expected = vec![
+            "[Content_Types].xml".to_owned(),
+            "_rels/.rels".to_owned(),
+            "some_pkg.nuspec".to_owned(),
+            core_properties_path("some_pkg", "0.1.1"),
+        ];
+        expected.sort();
+
+        assert_eq!(expected, entries);
+        assert!(nupkg.rids.is_empty());
+    }
+
+    #[test]
+    fn pack_lib_without_extension_uses_target() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/.gitignore"),
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert!(entry_names(&nupkg.buf).contains(&"runtimes/linux-x64/native/some_pkg.so".to_owned()));
+    }
+
+    #[test]
+    fn pack_with_libs_from_pairs() {
+        let spec = vec![].into();
+        let libs = vec![
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), PathBuf::from("tests/native/.gitignore")),
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), PathBuf::from("tests/native/Cargo.toml")),
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), PathBuf::from("tests/native/Cargo.toml")),
+        ];
+
+        let args = args(&spec).with_libs(libs);
+
+        assert_eq!(2, args.cargo_libs.len());
+
+        let nupkg = pack(args).unwrap();
+        let names = entry_names(&nupkg.buf);
+
+        assert!(names.contains(&"runtimes/linux-x64/native/some_pkg.toml".to_owned()));
+        assert!(names.contains(&"runtimes/win-x64/native/some_pkg.toml".to_owned()));
+    }
+
+    #[test]
+    fn pack_args_clone() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        let cloned = args.clone();
+
+        assert_eq!(args, cloned);
+    }
+
+    #[test]
+    fn pack_estimated_size() {
+        let dir = TempDir::new("pack-estimated-size");
+        let path = dir.join("native.so");
+        File::create(&path).unwrap().write_all(&[0; 1000]).unwrap();
+
+        let spec = vec![0; 24].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib(path.to_str().unwrap()),
+            ..args(&spec)
+        };
+
+        assert_eq!(STRUCTURE_SIZE_ESTIMATE + 1024, args.estimated_size().unwrap());
+    }
+
+    #[test]
+    fn pack_estimated_size_missing_lib() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/not-a-lib.so"),
+            ..args(&spec)
+        };
+
+        match args.estimated_size() {
+            Err(NugetPackError::WriteLib { err: NugetWriteLibError::Io(_), .. }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn pack_only_targets() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            only: Some(vec![Target::Cross(CrossTarget::Windows(Arch::x64))]),
+            ..args(&spec)
+        }.with_libs(vec![
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            (Target::Cross(CrossTarget::MacOS(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ]);
+
+        let nupkg = pack(args).unwrap();
+
+        assert_eq!(vec!["win-x64"], nupkg.rids);
+    }
+
+    #[test]
+    fn pack_only_targets_no_intersection() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            only: Some(vec![Target::Cross(CrossTarget::Windows(Arch::x64))]),
+            ..args(&spec)
+        };
+
+        assert_inavlid!(args, NugetPackError::NoValidTargets);
+    }
+
+    #[cfg(feature = "bzip2")]
+    #[test]
+    fn pack_bzip2_roundtrip() {
+        use std::io::Read;
+
+        let spec = b"<package />".to_vec().into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            compress_threshold: 0,
+            compression: NugetCompression::Bzip2,
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert_eq!(CompressionMethod::Bzip2, entry_compression(&nupkg.buf, "some_pkg.nuspec"));
+
+        let mut archive = ZipArchive::new(Cursor::new(&nupkg.buf[..])).unwrap();
+        let mut nuspec = Vec::new();
+        archive.by_name("some_pkg.nuspec").unwrap().read_to_end(&mut nuspec).unwrap();
+
+        assert_eq!(&spec[..], &nuspec[..]);
+    }
+
+    #[cfg(not(feature = "bzip2"))]
+    #[test]
+    fn pack_bzip2_without_feature() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            compression: NugetCompression::Bzip2,
+            ..args(&spec)
+        };
+
+        assert_inavlid!(args, NugetPackError::UnsupportedCompression { method: "bzip2", .. });
+    }
+
+    #[test]
+    fn pack_invalid_spec_encoding() {
+        let spec = vec![b'<', 0xff, 0xfe, b'>'].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        assert_inavlid!(args, NugetPackError::InvalidSpecEncoding { .. });
+    }
+
+    #[test]
+    fn pack_with_custom_entry_options() {
+        let spec = b"<package />".to_vec().into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        // Store the lib, but deflate everything else, including the small nuspec
+        let nupkg = pack_with_entry_options(args, |path, _| {
+            let method = match path.replace("\\", "/").starts_with("runtimes/") {
+                true => CompressionMethod::Stored,
+                false => CompressionMethod::Deflated,
+            };
+
+            FileOptions::default().compression_method(method)
+        }).unwrap();
+
+        assert_eq!(
+            CompressionMethod::Stored,
+            entry_compression(&nupkg.buf, "runtimes/linux-x64/native/some_pkg.toml")
+        );
+        assert_eq!(
+            CompressionMethod::Deflated,
+            entry_compression(&nupkg.buf, "some_pkg.nuspec")
+        );
+    }
+
+    #[test]
+    fn nuspec_entry_names() {
+        assert_eq!("some_pkg.nuspec", nuspec_entry_name("some_pkg"));
+        assert_eq!("Some.Pkg.nuspec", nuspec_entry_name("Some.Pkg"));
+    }
+
+    #[test]
+    fn pack_dotted_id() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            id: "Some.Pkg".into(),
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert!(entry_names(&nupkg.buf).contains(&"Some.Pkg.nuspec".to_owned()));
+    }
+
+    /// A cache that counts the libs read from it.
+    #[derive(Default)]
+    struct CountingPackCache {
+        cache: MemoryPackCache,
+        hits: usize,
+    }
+
+    impl PackCache for CountingPackCache {
+        fn get(&mut self, key: &PackCacheKey) -> Option<Vec<u8>> {
+            let lib = self.cache.get(key);
+
+            if lib.is_some() {
+                self.hits += 1;
+            }
+
+            lib
+        }
+
+        fn insert(&mut self, key: PackCacheKey, lib: &[u8]) {
+            self.cache.insert(key, lib)
+        }
+    }
+
+    #[test]
+    fn pack_cached_reuses_libs() {
+        let mut cache = CountingPackCache::default();
+
+        let spec = vec![].into();
+
+        for _ in 0..2 {
+            let args = NugetPackArgs {
+                cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+                ..args(&spec)
+            };
+
+            pack_cached(args, &mut cache).unwrap();
+        }
+
+        assert_eq!(1, cache.hits);
+    }
+
+    #[test]
+    fn pack_headers() {
+        let dir = TempDir::new("pack-headers");
+        let first = dir.join("first.h");
+        let second = dir.join("second.h");
+
+        File::create(&first).unwrap().write_all(b"void first();").unwrap();
+        File::create(&second).unwrap().write_all(b"void second();").unwrap();
+
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            headers: vec![first.into(), second.into()],
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+        let names = entry_names(&nupkg.buf);
+
+        assert!(names.contains(&"build/native/include/first.h".to_owned()));
+        assert!(names.contains(&"build/native/include/second.h".to_owned()));
+    }
+
+    #[test]
+    fn pack_headers_with_duplicate_names() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            headers: vec![Path::new("Cargo.toml").into(), Path::new("tests/native/Cargo.toml").into()],
+            ..args(&spec)
+        };
+
+        match pack(args) {
+            Err(NugetPackError::DuplicateHeader { ref name }) if name == "Cargo.toml" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn nupkg_meta_eq() {
+        let spec = vec![].into();
+        let first = pack(NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        }).unwrap();
+
+        let second = pack(NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            description: "A different description".into(),
+            ..args(&spec)
+        }).unwrap();
+
+        assert!(first != second);
+        assert!(first.meta_eq(&second));
+
+        let other_rids = pack(args(&spec).with_libs(vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ])).unwrap();
+
+        assert!(!first.meta_eq(&other_rids));
+    }
+
+    #[test]
+    fn nupkg_nuspec_bytes() {
+        use xml::reader::{EventReader, XmlEvent};
+
+        let spec = b"<package><metadata><id>Some.Pkg</id></metadata></package>".to_vec().into();
+        let args = NugetPackArgs {
+            id: "Some.Pkg".into(),
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+        let bytes = nupkg.nuspec_bytes().unwrap();
+
+        let mut in_id = false;
+        let mut id = None;
+        for event in EventReader::new(&bytes[..]) {
+            match event.unwrap() {
+                XmlEvent::StartElement { ref name, .. } => in_id = name.local_name == "id",
+                XmlEvent::Characters(ref text) if in_id => id = Some(text.clone()),
+                _ => in_id = false,
+            }
+        }
+
+        assert_eq!(Some("Some.Pkg".to_owned()), id);
+    }
+
+    #[test]
+    fn merge_runtimes_into_base() {
+        let spec = b"<package><metadata><id>some_pkg</id><version>0.1.1</version></metadata></package>".to_vec().into();
+        let base = pack(args(&spec).with_libs(vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ])).unwrap();
+
+        let mut new_libs = HashMap::new();
+        new_libs.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")),
+        );
+
+        let merged = merge_runtimes(&base.buf, new_libs, false, false).unwrap();
+
+        let names = entry_names(&merged.buf);
+
+        assert_eq!("some_pkg.0.1.1.nupkg", merged.name);
+        assert_eq!(vec!["linux-x64", "win-x64"], merged.rids);
+        assert!(names.contains(&"runtimes/win-x64/native/some_pkg.toml".to_owned()));
+        assert!(names.contains(&"runtimes/linux-x64/native/some_pkg.toml".to_owned()));
+        assert!(names.contains(&"some_pkg.nuspec".to_owned()));
+        assert_eq!(1, names.iter().filter(|name| *name == "[Content_Types].xml").count());
+    }
+
+    #[test]
+    fn merge_runtimes_keeps_base_layout_and_rid_overrides() {
+        let spec = b"<package><metadata><version>0.1.1</version></metadata></package>".to_vec().into();
+        let base = pack(NugetPackArgs {
+            layout: NugetLibLayout::Lib {
+                tfm: "netstandard2.0".into(),
+            },
+            ..args(&spec)
+        }.with_libs(vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ])).unwrap();
+
+        let mut new_libs = HashMap::new();
+        new_libs.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")).with_rid("ubuntu.22.04-x64").unwrap(),
+        );
+
+        let merged = merge_runtimes(&base.buf, new_libs, false, false).unwrap();
+
+        assert!(entry_names(&merged.buf).contains(&"runtimes/ubuntu.22.04-x64/lib/netstandard2.0/some_pkg.toml".to_owned()));
+        assert_eq!(vec!["ubuntu.22.04-x64", "win-x64"], merged.rids);
+    }
+
+    #[test]
+    fn merge_runtimes_rid_collision() {
+        let spec = b"<package><metadata><version>0.1.1</version></metadata></package>".to_vec().into();
+        let base = pack(args(&spec).with_libs(vec![
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ])).unwrap();
+
+        let mut new_libs = HashMap::new();
+        new_libs.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")),
+        );
+
+        match merge_runtimes(&base.buf, new_libs, false, false) {
+            Err(NugetPackError::RidCollision { ref rid }) if rid == "linux-x64" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn merge_runtimes_new_lib_rid_collision() {
+        let spec = b"<package><metadata><version>0.1.1</version></metadata></package>".to_vec().into();
+        let base = pack(args(&spec).with_libs(vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ])).unwrap();
+
+        let mut new_libs = HashMap::new();
+        new_libs.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")).with_rid("ubuntu.22.04-x64").unwrap(),
+        );
+        new_libs.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x86)),
+            NugetLib::new(Path::new("tests/native/Cargo.toml")).with_rid("ubuntu.22.04-x64").unwrap(),
+        );
+
+        match merge_runtimes(&base.buf, new_libs, false, false) {
+            Err(NugetPackError::RidCollision { ref rid }) if rid == "ubuntu.22.04-x64" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn merge_runtimes_checks_expected_digest() {
+        let spec = b"<package><metadata><version>0.1.1</version></metadata></package>".to_vec().into();
+        let base = pack(args(&spec).with_libs(vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ])).unwrap();
+
+        let expected = "0".repeat(64);
+
+        let mut new_libs = HashMap::new();
+        new_libs.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib {
+                sha256: Some(expected.clone()),
+                ..NugetLib::new(Path::new("tests/native/Cargo.toml"))
+            },
+        );
+
+        match merge_runtimes(&base.buf, new_libs, false, false) {
+            Err(NugetPackError::WriteLib {
+                err: NugetWriteLibError::DigestMismatch { expected: ref e, .. },
+                ..
+            }) if *e == expected => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn pack_embedded_manifest() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            embed_manifest: Some(Cow::Borrowed(Path::new("tests/native/Cargo.toml"))),
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(&nupkg.buf[..])).unwrap();
+        let mut embedded = Vec::new();
+        archive.by_name(EMBEDDED_MANIFEST_PATH).unwrap().read_to_end(&mut embedded).unwrap();
+
+        let mut source = Vec::new();
+        File::open("tests/native/Cargo.toml").unwrap().read_to_end(&mut source).unwrap();
+
+        assert_eq!(source, embedded);
+
+        let mut content_types = String::new();
+        archive.by_name("[Content_Types].xml").unwrap().read_to_string(&mut content_types).unwrap();
+
+        assert!(content_types.contains(r#"Extension="orig""#));
+    }
+
+    #[test]
+    fn pack_runtime_json() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            runtime_json: true,
+            ..args(&spec)
+        }.with_libs(vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ]);
+
+        let nupkg = pack(args).unwrap();
+
+        let mut archive = ZipArchive::new(Cursor::new(&nupkg.buf[..])).unwrap();
+        let mut graph = String::new();
+        archive.by_name(RUNTIME_JSON_PATH).unwrap().read_to_string(&mut graph).unwrap();
+
+        let expected = r##"{
+  "runtimes": {
+    "linux-x64": { "#import": ["linux", "unix-x64", "unix", "any", "base"] },
+    "win-x64": { "#import": ["win", "any", "base"] }
+  }
+}
+"##;
+
+        assert_eq!(expected, graph);
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(r#""linux-x64""#, json_string("linux-x64"));
+        assert_eq!(r#""a\"b\\c\u000a""#, json_string("a\"b\\c\n"));
+    }
+
+    #[test]
+    fn pack_sizes() {
+        let dir = TempDir::new("pack-sizes");
+        let path = dir.join("native.so");
+        File::create(&path).unwrap().write_all(&[42; 4096]).unwrap();
+
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib(path.to_str().unwrap()),
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert_eq!(nupkg.buf.len() as u64, nupkg.compressed_size);
+        assert!(nupkg.uncompressed_size > nupkg.compressed_size);
+    }
+
+    #[test]
+    fn pack_entry_stats() {
+        // A simple xorshift, so the lib's bytes don't have patterns to compress
+        let mut state = 0x2545_f491_u32;
+        let lib: Vec<u8> = (0..8192)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+
+                state as u8
+            })
+            .collect();
+
+        let dir = TempDir::new("pack-entry-stats");
+        let path = dir.join("native.so");
+        File::create(&path).unwrap().write_all(&lib).unwrap();
+
+        let spec = format!("<package><metadata>{}</metadata></package>", "<tag>rust</tag>".repeat(256));
+        let spec = spec.into_bytes().into();
+
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib(path.to_str().unwrap()),
+            entry_stats: true,
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+        let stats = nupkg.entry_stats.unwrap();
+
+        let stat = |name: &str| stats.iter().find(|stat| stat.name == name).unwrap().clone();
+
+        let nuspec = stat("some_pkg.nuspec");
+        assert_eq!(spec.len() as u64, nuspec.uncompressed_size);
+        assert!(nuspec.ratio() < 0.1);
+
+        let lib = stat("runtimes/linux-x64/native/some_pkg.so");
+        assert_eq!(8192, lib.uncompressed_size);
+        assert!(lib.ratio() > 0.9);
+    }
+
+    #[test]
+    fn pack_without_entry_stats() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert_eq!(None, nupkg.entry_stats);
+    }
+
+    #[test]
+    fn pack_entry_order() {
+        let spec = vec![].into();
+        let args = args(&spec).with_libs(vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            (Target::Cross(CrossTarget::MacOS(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+        ]);
+
+        let nupkg = pack(args).unwrap();
+
+        let expected = vec![
+            "[Content_Types].xml",
+            "_rels/.rels",
+            "some_pkg.nuspec",
+            "runtimes/linux-x64/native/some_pkg.toml",
+            "runtimes/osx-x64/native/some_pkg.toml",
+            "runtimes/win-x64/native/some_pkg.toml",
+        ];
+
+        let mut expected: Vec<String> = expected.into_iter().map(|name| name.to_owned()).collect();
+        expected.push(core_properties_path("some_pkg", "0.1.1"));
+
+        assert_eq!(expected, entry_names(&nupkg.buf));
+    }
+
+    #[test]
+    fn pack_core_properties() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        let path = core_properties_path("some_pkg", "0.1.1");
+        assert!(path.starts_with("package/services/metadata/core-properties/"));
+        assert!(entry_names(&nupkg.buf).contains(&path));
+
+        let mut archive = ZipArchive::new(Cursor::new(&nupkg.buf[..])).unwrap();
+        let mut rels = String::new();
+        archive.by_name("_rels/.rels").unwrap().read_to_string(&mut rels).unwrap();
+
+        assert!(rels.contains("/metadata/core-properties\""));
+        assert!(rels.contains(&format!("Target=\"/{}\"", path)));
+    }
+
+    #[test]
+    fn pack_native_layout() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert!(entry_names(&nupkg.buf).contains(&"runtimes/linux-x64/native/some_pkg.toml".to_owned()));
+    }
+
+    #[test]
+    fn pack_lib_layout() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            layout: NugetLibLayout::Lib {
+                tfm: "netstandard2.0".into(),
+            },
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert!(entry_names(&nupkg.buf).contains(&"runtimes/linux-x64/lib/netstandard2.0/some_pkg.toml".to_owned()));
+    }
+
+    #[test]
+    fn pack_lib_entry_names_use_forward_slashes() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            id: "Some.Pkg".into(),
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            layout: NugetLibLayout::Lib {
+                tfm: "netstandard2.0".into(),
+            },
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        // Entry names aren't normalized here, so a host separator would show up
+        let mut archive = ZipArchive::new(Cursor::new(&nupkg.buf[..])).unwrap();
+        let names: Vec<_> = (0..archive.len())
+            .map(|i| archive.by_index(i).unwrap().name().to_owned())
+            .filter(|name| name.starts_with("runtimes"))
+            .collect();
+
+        assert_eq!(vec!["runtimes/linux-x64/lib/netstandard2.0/Some.Pkg.toml"], names);
+    }
+
+    #[test]
+    fn pack_stores_small_entries() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("src/args.rs"),
+            compress_threshold: 1024,
+            ..args(&spec)
+        };
+
+        let nupkg = pack(args).unwrap();
+
+        assert_eq!(
+            CompressionMethod::Stored,
+            entry_compression(&nupkg.buf, "_rels/.rels")
+        );
+        assert_eq!(
+            CompressionMethod::Deflated,
+            entry_compression(&nupkg.buf, "runtimes/linux-x64/native/some_pkg.rs")
+        );
+    }
+
+    #[test]
+    fn pack_error_source_chain() {
+        use std::error::Error;
+
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib("not a lib"),
+            ..args(&spec)
+        };
+
+        let err = pack(args).unwrap_err();
+
+        let lib_err = err.source().expect("missing lib error");
+        assert!(lib_err.downcast_ref::<NugetWriteLibError>().is_some());
+
+        let io_err = lib_err.source().expect("missing io error");
+        assert!(io_err.downcast_ref::<IoError>().is_some());
+        assert!(io_err.source().is_none());
+    }
+
+    #[test]
+    fn pack_arch_mismatch() {
+        let lib = binary::fake_lib(Target::Cross(CrossTarget::Linux(Arch::arm64)));
+
+        let dir = TempDir::new("pack-arch-mismatch");
+        let path = dir.join("native.so");
+        File::create(&path).unwrap().write_all(&lib).unwrap();
+
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            cargo_libs: linux_x64_lib(path.to_str().unwrap()),
+            check_arch: true,
+            ..args(&spec)
+        };
+
+        match pack(args) {
+            Err(NugetPackError::WriteLib {
+                err: NugetWriteLibError::ArchMismatch {
+                    expected: Arch::x64,
+                    actual: Arch::arm64,
+                },
+                ..
+            }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn pack_check_arch_fake_libs() {
+        let dir = TempDir::new("pack-check-arch");
+
+        for &arch in &[Arch::x86, Arch::x64, Arch::arm64] {
+            for &cross in &[CrossTarget::Linux(arch), CrossTarget::MacOS(arch), CrossTarget::Windows(arch)] {
+                let target = Target::Cross(cross);
+
+                let path = dir.join(&*target.rid());
+                File::create(&path).unwrap().write_all(&binary::fake_lib(target)).unwrap();
+
+                let spec = vec![].into();
+                let args = NugetPackArgs {
+                    check_arch: true,
+                    ..args(&spec)
+                }.with_libs(vec![(target, path.as_path())]);
+
+                let nupkg = pack(args).unwrap();
+
+                assert_eq!(vec![target.rid()], nupkg.rids);
+            }
+        }
+    }
+
+    #[test]
+    fn pack_variants_per_version() {
+        let spec = b"<package><metadata><version>1.0.0</version></metadata></package>".to_vec().into();
+        let args = NugetPackArgs {
+            version: "1.0.0".into(),
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        let versions = vec!["1.0.0".to_owned(), "1.0.0-dev.5".to_owned()];
+
+        let nupkgs = pack_variants(args, &versions).unwrap();
+
+        let names: Vec<_> = nupkgs.iter().map(|nupkg| nupkg.name.as_ref()).collect();
+        assert_eq!(vec!["some_pkg.1.0.0.nupkg", "some_pkg.1.0.0-dev.5.nupkg"], names);
+
+        let mut archive = ZipArchive::new(Cursor::new(&*nupkgs[1].buf)).unwrap();
+        let mut nuspec = String::new();
+        archive
+            .by_name("some_pkg.nuspec")
+            .unwrap()
+            .read_to_string(&mut nuspec)
+            .unwrap();
+
+        assert!(nuspec.contains("<version>1.0.0-dev.5</version>"));
+    }
+
+    #[test]
+    fn pack_variants_spec_without_version() {
+        let spec = b"<package><metadata><version>0.9.0</version></metadata></package>".to_vec().into();
+        let args = NugetPackArgs {
+            version: "1.0.0".into(),
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        let versions = vec!["1.0.0-dev.5".to_owned()];
+
+        match pack_variants(args, &versions) {
+            Err(NugetPackError::SpecVersionMismatch { ref version }) if version == "<version>1.0.0</version>" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn pack_malicious_id() {
+        let spec = vec![].into();
+        let args = NugetPackArgs {
+            id: "../../evil".into(),
+            cargo_libs: linux_x64_lib("tests/native/Cargo.toml"),
+            ..args(&spec)
+        };
+
+        match pack(args) {
+            Err(NugetPackError::WriteLib {
+                err: NugetWriteLibError::BadPath { .. },
+                ..
+            }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn write_lib_malicious_rid() {
+        let spec = vec![].into();
+        let args = args(&spec);
+
+        let lib = Lib {
+            target: Target::Cross(CrossTarget::Linux(Arch::x64)),
+            rid: "../../evil".into(),
+            path: Path::new("lib.so"),
+            buf: vec![],
+        };
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        match write_lib(&mut writer, &args, &lib, &|_, len| options(len, DEFAULT_COMPRESS_THRESHOLD, CompressionMethod::Deflated)) {
+            Err(NugetWriteLibError::BadPath { ref path }) if path == "../../evil" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn lib_entry_names() {
+        assert_eq!(
+            "runtimes/linux-x64/native/Some.Pkg.so",
+            lib_entry_name("Some.Pkg", &NugetLibLayout::Native, "linux-x64", Some("so")).unwrap()
+        );
+
+        let layout = NugetLibLayout::Lib {
+            tfm: "netstandard2.0".into(),
+        };
+        assert_eq!(
+            "runtimes/osx-x64/lib/netstandard2.0/some_pkg",
+            lib_entry_name("some_pkg", &layout, "osx-x64", None).unwrap()
+        );
+
+        assert!(lib_entry_name("some_pkg", &NugetLibLayout::Native, "../evil", None).is_err());
+
+        let layout = NugetLibLayout::Lib { tfm: "../evil".into() };
+        assert!(lib_entry_name("some_pkg", &layout, "linux-x64", None).is_err());
+    }
+
+    #[test]
+    fn path_components() {
+        assert!(path_component("native_test").is_ok());
+        assert!(path_component("osx.11.0-arm64").is_ok());
+
+        for component in &["", ".", "..", "a/b", "a\\b", "/evil", "C:"] {
+            assert!(path_component(component).is_err(), "{}", component);
+        }
+    }
+
+    #[test]
+    fn pack_error_codes() {
+        use std::io::ErrorKind;
+
+        let bytes = vec![0xff];
+
+        let cases = vec![
+            (NugetPackError::NoValidTargets, "E_NUPKG_NO_TARGETS"),
+            (NugetPackError::Zip(ZipError::FileNotFound), "E_NUPKG_ZIP"),
+            (
+                NugetPackError::Io(IoError::new(ErrorKind::Other, "io")),
+                "E_NUPKG_IO",
+            ),
+            (
+                NugetPackError::Xml(xml::Error::DocumentStartAlreadyEmitted),
+                "E_NUPKG_XML",
+            ),
+            (
+                NugetPackError::WriteLib {
+                    rid: "linux-x64".into(),
+                    lib_path: "lib.so".into(),
+                    err: NugetWriteLibError::Io(IoError::new(ErrorKind::Other, "io")),
+                },
+                "E_NUPKG_WRITE_LIB",
+            ),
+            (
+                NugetPackError::WriteHeader {
+                    header_path: "native.h".into(),
+                    err: NugetWriteLibError::Io(IoError::new(ErrorKind::Other, "io")),
+                },
+                "E_NUPKG_WRITE_HEADER",
+            ),
+            (
+                NugetPackError::DuplicateHeader { name: "native.h".into() },
+                "E_NUPKG_DUPLICATE_HEADER",
+            ),
+            (
+                NugetPackError::EmbedManifest {
+                    manifest_path: "Cargo.toml".into(),
+                    err: IoError::new(ErrorKind::Other, "io"),
+                },
+                "E_NUPKG_EMBED_MANIFEST",
+            ),
+            (
+                NugetPackError::UnsafeEntry { name: "../evil".into() },
+                "E_NUPKG_UNSAFE_ENTRY",
+            ),
+            (
+                NugetPackError::InvalidRid { rid: "../evil".into() },
+                "E_NUPKG_INVALID_RID",
+            ),
+            (
+                NugetPackError::SpecVersionMismatch { version: "0.1.0".into() },
+                "E_NUPKG_SPEC_VERSION",
+            ),
+            (
+                NugetPackError::RidCollision { rid: "linux-x64".into() },
+                "E_NUPKG_RID_COLLISION",
+            ),
+            (
+                NugetPackError::InvalidBase {
+                    reason: "it doesn't contain a nuspec",
+                },
+                "E_NUPKG_INVALID_BASE",
+            ),
+            (
+                NugetPackError::UnsupportedCompression {
+                    method: "bzip2",
+                    feature: "bzip2",
+                },
+                "E_NUPKG_UNSUPPORTED_COMPRESSION",
+            ),
+            (
+                NugetPackError::InvalidSpecEncoding {
+                    err: str::from_utf8(&bytes).unwrap_err(),
+                },
+                "E_NUPKG_SPEC_ENCODING",
+            ),
+        ];
+
+        for (err, code) in cases {
+            assert_eq!(code, err.code());
+        }
     }
 }
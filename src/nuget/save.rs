@@ -1,9 +1,10 @@
 use std::borrow::Cow;
-use std::path::Path;
-use std::io::{Error as IoError, Write};
-use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::io::{Error as IoError, ErrorKind, Write};
+use std::fs::{self, File};
 
-use super::Buf;
+use super::{Buf, Nupkg};
+use super::util::file;
 
 /// Args for saving a `nupkg` to a file.
 #[derive(Debug, PartialEq)]
@@ -18,29 +19,151 @@ pub struct NupkgPath<'a> {
     pub path: Cow<'a, Path>,
 }
 
-/// Format the input as a `nuspec` xml buffer.
+/// Save a `nupkg` to a file.
+///
+/// The parent directory is created if it doesn't already exist.
 pub fn save_nupkg<'a>(args: NugetSaveArgs<'a>) -> Result<NupkgPath<'a>, NugetSaveError> {
-    let mut f = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(&args.path)?;
+    if let Some(dir) = args.path.parent() {
+        create_dir(dir)?;
+    }
 
-    f.write_all(&args.nupkg)?;
+    write_file(&args.path, &args.nupkg)?;
 
     info!("nupkg written to: {:?}", args.path);
 
     Ok(NupkgPath { path: args.path })
 }
 
+impl<'a> Nupkg<'a> {
+    /// Write the `nupkg` to `dir/{name}`, overwriting any existing file.
+    ///
+    /// The directory is created if it doesn't already exist.
+    /// The full path to the written file is returned.
+    pub fn write_to_file(&self, dir: &Path) -> Result<PathBuf, NugetSaveError> {
+        create_dir(dir)?;
+
+        let path = dir.join(self.name.as_ref());
+
+        write_file(&path, &self.buf)?;
+
+        Ok(path)
+    }
+}
+
+/// Recursively create a directory.
+fn create_dir(dir: &Path) -> Result<(), NugetSaveError> {
+    if dir.as_os_str().is_empty() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir).map_err(|e| io_error(dir, e))
+}
+
+/// Write a buffer to a file, overwriting any existing file.
+fn write_file(path: &Path, buf: &[u8]) -> Result<(), NugetSaveError> {
+    write_file_with(path, |f| f.write_all(buf))
+}
+
+/// Write a file by writing to a temporary file alongside it, then renaming it into place.
+///
+/// If writing fails then the temporary file is removed, so a partial file is never left at `path`.
+fn write_file_with<F>(path: &Path, write: F) -> Result<(), NugetSaveError>
+where
+    F: FnOnce(&mut File) -> Result<(), IoError>,
+{
+    file::write_with(path, write).map_err(|e| io_error(path, e))
+}
+
+fn io_error(path: &Path, err: IoError) -> NugetSaveError {
+    match err.kind() {
+        ErrorKind::PermissionDenied => NugetSaveError::PermissionDenied {
+            path: path.to_string_lossy().into_owned(),
+            err: err,
+        },
+        _ => NugetSaveError::Io(err),
+    }
+}
+
 quick_error!{
     #[derive(Debug)]
     pub enum NugetSaveError {
         /// An io-related error writing to a file.
         Io (err: IoError) {
-            cause(err)
+            source(err)
             display("Error saving nupkg\nCaused by: {}", err)
             from()
         }
+        /// The nupkg couldn't be written because of missing permissions.
+        PermissionDenied { path: String, err: IoError } {
+            source(err)
+            display("Permission denied saving nupkg to '{}'\nCaused by: {}", path, err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempdir::TempDir;
+    use super::*;
+
+    #[test]
+    fn write_nupkg_to_file() {
+        let temp = TempDir::new("write-to-file");
+        let dir = temp.path();
+
+        let nupkg = Nupkg {
+            name: "some_pkg.0.1.0.nupkg".into(),
+            rids: vec![],
+            compressed_size: 4,
+            uncompressed_size: 4,
+            entry_stats: None,
+            buf: vec![1, 2, 3, 4].into(),
+        };
+
+        // Write twice to make sure existing files are overwritten
+        nupkg.write_to_file(dir).unwrap();
+        let path = nupkg.write_to_file(dir).unwrap();
+
+        assert_eq!(dir.join("some_pkg.0.1.0.nupkg"), path);
+        assert_eq!(4, fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn write_nupkg_to_nested_dir() {
+        let root = TempDir::new("write-to-nested-dir");
+
+        let dir = root.join("some").join("nested").join("dir");
+
+        let nupkg = Nupkg {
+            name: "some_pkg.0.1.0.nupkg".into(),
+            rids: vec![],
+            compressed_size: 4,
+            uncompressed_size: 4,
+            entry_stats: None,
+            buf: vec![1, 2, 3, 4].into(),
+        };
+
+        let path = nupkg.write_to_file(&dir).unwrap();
+
+        assert_eq!(dir.join("some_pkg.0.1.0.nupkg"), path);
+        assert_eq!(4, fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn write_file_failure_leaves_no_partial_file() {
+        let dir = TempDir::new("write-file-failure");
+
+        let path = dir.join("some_pkg.0.1.0.nupkg");
+
+        let result = write_file_with(&path, |f| {
+            f.write_all(&[1, 2])?;
+
+            Err(IoError::new(ErrorKind::Other, "failed mid-write"))
+        });
+
+        assert!(result.is_err());
+        assert!(!path.exists());
+        assert!(!file::tmp_path(&path).exists());
     }
 }
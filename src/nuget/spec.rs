@@ -3,9 +3,11 @@
 use std::ops::Deref;
 use std::io::Error as IoError;
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 
-use super::Buf;
+use super::{lib_entry_name, lib_extension, Buf, NugetLib, NugetLibLayout, NugetWriteLibError};
 use super::util::xml;
+use args::Target;
 
 /// Nuget package dependency.
 #[derive(Debug, PartialEq)]
@@ -39,27 +41,201 @@ impl<'a> Deref for NugetDependencies<'a> {
     }
 }
 
+/// A reference to a shared .NET framework, like `Microsoft.AspNetCore.App`.
+#[derive(Debug, PartialEq)]
+pub struct NugetFrameworkReference<'a> {
+    pub target_framework: Cow<'a, str>,
+    pub name: Cow<'a, str>,
+}
+
+/// A file listed in the nuspec `<files>` section.
+#[derive(Debug, PartialEq)]
+pub struct NugetFile<'a> {
+    pub src: Cow<'a, str>,
+    pub target: Cow<'a, str>,
+}
+
 /// Args for building a `nuspec` metadata file.
 #[derive(Debug, PartialEq)]
 pub struct NugetSpecArgs<'a> {
     pub id: Cow<'a, str>,
     pub version: Cow<'a, str>,
-    pub authors: Cow<'a, str>,
+    /// The package authors, joined with `author_separator` when formatting.
+    pub authors: Vec<Cow<'a, str>>,
+    /// The package owners, joined with `author_separator` when formatting.
+    ///
+    /// Nothing is written if there are no owners.
+    pub owners: Vec<Cow<'a, str>>,
     pub description: Cow<'a, str>,
     pub repository: Cow<'a, str>,
+    pub project_url: Option<Cow<'a, str>>,
+    pub language: Option<Cow<'a, str>>,
+    pub serviceable: bool,
+    pub min_client_version: Option<Cow<'a, str>>,
+    pub release_notes: Option<Cow<'a, str>>,
+    pub tags: Vec<Cow<'a, str>>,
     pub dependencies: NugetDependencies<'a>,
+    pub framework_references: Vec<NugetFrameworkReference<'a>>,
+    /// Custom metadata elements, written as `<{name}>{value}</{name}>`.
+    pub extra_metadata: Vec<(String, String)>,
+    /// Descriptions longer than this many characters are formatted with a warning.
+    pub max_description_len: usize,
+    /// The types of package, like `Dependency` or `DotnetTool`.
+    ///
+    /// Nothing is written if there are no package types, which NuGet treats as `Dependency`.
+    /// There's no default `Dependency` entry so existing nuspecs don't change.
+    /// Names aren't checked against the types NuGet knows about, because NuGet also
+    /// allows custom package types, so any valid identifier is accepted.
+    pub package_types: Vec<String>,
+    /// Allow dependency version ranges to reference prerelease versions.
+    pub allow_prerelease_deps: bool,
+    /// Files to list in a `<files>` section.
+    ///
+    /// Nothing is written if there are no files.
+    pub files: Vec<NugetFile<'a>>,
+    /// The separator to join authors and owners with.
+    pub author_separator: char,
+    /// A human-friendly title for the package.
+    ///
+    /// NuGet displays the id if there's no title.
+    pub title: Option<Cow<'a, str>>,
+}
+
+impl<'a> NugetSpecArgs<'a> {
+    /// List the runtime libs that will be packed as `<file>` entries.
+    ///
+    /// Files are sorted by their target path and unknown targets are ignored, like they are when packing.
+    /// Targets are named the same way as the entries libs are packed into, including any rid overrides.
+    pub fn with_lib_files(
+        mut self,
+        layout: &NugetLibLayout,
+        libs: &HashMap<Target, NugetLib>,
+    ) -> Result<Self, NugetSpecError> {
+        let mut files = Vec::with_capacity(libs.len());
+
+        for (target, lib) in libs.iter().filter(|&(target, _)| !target.is_unknown()) {
+            let rid = lib.resolved_rid(target);
+            let extension = lib_extension(target, &lib.path);
+
+            let name = lib_entry_name(&self.id, layout, &rid, extension.as_ref().map(|e| &**e))
+                .map_err(|e| NugetSpecError::InvalidLibFile {
+                    path: lib.path.to_string_lossy().into_owned(),
+                    err: e,
+                })?;
+
+            files.push(NugetFile {
+                src: lib.path.to_string_lossy().into_owned().into(),
+                target: name.into(),
+            });
+        }
+
+        files.sort_by(|a, b| a.target.cmp(&b.target));
+
+        self.files = files;
+        Ok(self)
+    }
 }
 
+/// The separator NuGet conventionally uses between authors.
+pub const DEFAULT_AUTHOR_SEPARATOR: char = ',';
+
+/// Join authors or owners with a separator, like `Someone, Someone Else`.
+///
+/// The joined string is allocated once up-front, so long lists are cheap to join.
+pub fn join_authors<S>(authors: &[S], separator: char) -> String
+where
+    S: AsRef<str>,
+{
+    let separator_len = separator.len_utf8() + 1;
+    let len = authors.iter().map(|author| author.as_ref().len() + separator_len).sum::<usize>();
+
+    let mut joined = String::with_capacity(len);
+
+    for (i, author) in authors.iter().enumerate() {
+        if i > 0 {
+            joined.push(separator);
+            joined.push(' ');
+        }
+
+        joined.push_str(author.as_ref());
+    }
+
+    joined
+}
+
+/// The number of characters NuGet displays in a package description.
+pub const DEFAULT_MAX_DESCRIPTION_LEN: usize = 4000;
+
 /// A formatted nuspec file.
 #[derive(Debug, PartialEq)]
 pub struct Nuspec<'a> {
     pub id: Cow<'a, str>,
     pub version: Cow<'a, str>,
+    pub authors: Cow<'a, str>,
+    pub description: Cow<'a, str>,
     pub xml: Buf,
+    pub warnings: Vec<String>,
 }
 
 /// Format the input as a `nuspec` xml buffer.
 pub fn spec<'a>(args: NugetSpecArgs<'a>) -> Result<Nuspec<'a>, NugetSpecError> {
+    if let Some(ref language) = args.language {
+        if !is_language_tag(language) {
+            Err(NugetSpecError::InvalidLanguage {
+                language: language.to_string(),
+            })?;
+        }
+    }
+
+    if let Some(ref min_client_version) = args.min_client_version {
+        if !is_client_version(min_client_version) {
+            Err(NugetSpecError::InvalidMinClientVersion {
+                version: min_client_version.to_string(),
+            })?;
+        }
+    }
+
+    if !is_author_separator(args.author_separator) {
+        Err(NugetSpecError::InvalidAuthorSeparator {
+            separator: args.author_separator,
+        })?;
+    }
+
+    for &(ref name, _) in &args.extra_metadata {
+        if !is_extra_metadata_name(name) {
+            Err(NugetSpecError::InvalidMetadataElement { name: name.clone() })?;
+        }
+    }
+
+    for package_type in &args.package_types {
+        if !is_package_type(package_type) {
+            Err(NugetSpecError::InvalidPackageType {
+                name: package_type.clone(),
+            })?;
+        }
+    }
+
+    if !args.allow_prerelease_deps {
+        if let Some(dependency) = args.dependencies.iter().find(|dep| is_prerelease_range(&dep.version)) {
+            Err(NugetSpecError::PrereleaseDependency {
+                id: dependency.id.to_string(),
+                version: dependency.version.to_string(),
+            })?;
+        }
+    }
+
+    let authors = join_authors(&args.authors, args.author_separator);
+
+    let mut warnings = Vec::new();
+
+    let description_len = args.description.chars().count();
+    if description_len > args.max_description_len {
+        warnings.push(format!(
+            "The description is {} characters long, but only {} may be displayed",
+            description_len, args.max_description_len
+        ));
+    }
+
     let mut writer = xml::writer()?;
 
     let pkg_attr = xml::attr(
@@ -67,27 +243,220 @@ pub fn spec<'a>(args: NugetSpecArgs<'a>) -> Result<Nuspec<'a>, NugetSpecError> {
         "http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd",
     );
 
+    let metadata_attrs: Vec<_> = args.min_client_version
+        .iter()
+        .map(|version| xml::attr("minClientVersion", version))
+        .collect();
+
     xml::elem(&mut writer, "package", &[pkg_attr], |ref mut writer| {
-        xml::elem(writer, "metadata", &[], |ref mut writer| {
-            format_meta(&args, writer)?;
-            format_dependencies(&args.dependencies, writer)
-        })
+        xml::elem(writer, "metadata", &metadata_attrs, |ref mut writer| {
+            format_meta(&args, &authors, writer)?;
+            format_package_types(&args.package_types, writer)?;
+            format_dependencies(&args.dependencies, writer)?;
+            format_framework_references(&args.framework_references, writer)
+        })?;
+
+        format_files(&args.files, writer)
     })?;
 
     Ok(Nuspec {
         id: args.id,
         version: args.version,
+        authors: Cow::Owned(authors),
+        description: args.description,
         xml: writer.into_inner().into(),
+        warnings: warnings,
     })
 }
 
 /// Write basic nuspec metadata.
-fn format_meta<'a>(args: &NugetSpecArgs<'a>, writer: &mut xml::Writer) -> Result<(), xml::Error> {
+fn format_meta<'a>(args: &NugetSpecArgs<'a>, authors: &str, writer: &mut xml::Writer) -> Result<(), xml::Error> {
     xml::val(writer, "id", &args.id)?;
     xml::val(writer, "version", &args.version)?;
-    xml::val(writer, "authors", &args.authors)?;
+
+    if let Some(ref title) = args.title {
+        xml::val(writer, "title", title)?;
+    }
+
+    xml::val(writer, "authors", &authors)?;
+
+    if !args.owners.is_empty() {
+        xml::val(writer, "owners", &join_authors(&args.owners, args.author_separator))?;
+    }
+
     xml::elem(writer, "repository", &[xml::attr("url", &args.repository)], |_| { Ok(()) })?;
-    xml::val(writer, "description", &args.description)
+
+    if let Some(ref project_url) = args.project_url {
+        xml::val(writer, "projectUrl", project_url)?;
+    }
+
+    if let Some(ref language) = args.language {
+        xml::val(writer, "language", language)?;
+    }
+
+    if args.serviceable {
+        xml::val(writer, "serviceable", &"true")?;
+    }
+
+    xml::val(writer, "description", &args.description)?;
+
+    if let Some(ref release_notes) = args.release_notes {
+        xml::val(writer, "releaseNotes", release_notes)?;
+    }
+
+    if !args.tags.is_empty() {
+        xml::val(writer, "tags", &args.tags.join(" "))?;
+    }
+
+    for &(ref name, ref value) in &args.extra_metadata {
+        xml::val(writer, name, value)?;
+    }
+
+    Ok(())
+}
+
+/// Merge tags from multiple sources.
+///
+/// Tags that only differ by case are duplicates, and the first one seen is kept.
+pub fn merge_tags<'a, I>(tags: I) -> Vec<Cow<'a, str>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut merged: Vec<Cow<'a, str>> = Vec::new();
+    let mut seen = HashSet::new();
+
+    for tag in tags {
+        let tag = tag.trim();
+
+        if tag.is_empty() || !seen.insert(tag.to_lowercase()) {
+            continue;
+        }
+
+        merged.push(Cow::Borrowed(tag));
+    }
+
+    merged
+}
+
+/// Check whether a language looks like a BCP-47 tag, like `en` or `en-US`.
+///
+/// This is a loose check on the shape of the tag, not its registered subtags.
+fn is_language_tag(language: &str) -> bool {
+    let mut subtags = language.split('-');
+
+    let primary = subtags.next().unwrap_or("");
+    let primary_valid = primary.len() >= 2 && primary.len() <= 8
+        && primary.chars().all(|c| c.is_ascii_alphabetic());
+
+    primary_valid && subtags.all(|subtag| {
+        subtag.len() >= 1 && subtag.len() <= 8 && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+/// Check whether either bound of a version range, like `[1.0.0-beta, )`, is a prerelease.
+fn is_prerelease_range(range: &str) -> bool {
+    range
+        .split(',')
+        .map(|bound| bound.trim_matches(|c: char| c == '[' || c == ']' || c == '(' || c == ')' || c.is_whitespace()))
+        .any(|bound| bound.splitn(2, '+').next().unwrap_or("").contains('-'))
+}
+
+/// Check whether a version looks like a NuGet client version, like `2.12` or `3.3.0`.
+fn is_client_version(version: &str) -> bool {
+    let parts: Vec<_> = version.split('.').collect();
+
+    parts.len() >= 2 && parts.len() <= 4
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Check whether a name is a valid package type, like `Dependency` or `MyOrg.Plugin`.
+///
+/// Custom package types are allowed, so only the shape of the name is checked.
+fn is_package_type(name: &str) -> bool {
+    let mut chars = name.chars();
+    let first_valid = chars.next().map(|c| c.is_ascii_alphabetic()).unwrap_or(false);
+    first_valid && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Check whether a character can separate authors.
+///
+/// Separators are punctuation that doesn't need escaping in xml, like `,` or `;`.
+fn is_author_separator(separator: char) -> bool {
+    separator.is_ascii_punctuation() && !"<>&\"'".contains(separator)
+}
+
+/// Elements nuspec metadata already defines, which can't be used for extra metadata.
+const RESERVED_METADATA_ELEMENTS: &'static [&'static str] = &[
+    "id",
+    "version",
+    "title",
+    "authors",
+    "owners",
+    "description",
+    "summary",
+    "releaseNotes",
+    "copyright",
+    "language",
+    "tags",
+    "serviceable",
+    "repository",
+    "projectUrl",
+    "license",
+    "licenseUrl",
+    "icon",
+    "iconUrl",
+    "readme",
+    "requireLicenseAcceptance",
+    "developmentDependency",
+    "packageTypes",
+    "dependencies",
+    "frameworkReferences",
+    "frameworkAssemblies",
+    "references",
+    "contentFiles",
+];
+
+/// Check whether a name can be used for an extra metadata element, like `buildDate`.
+///
+/// Names must be valid XML element names without a namespace prefix. Names reserved by XML,
+/// which start with `xml`, and elements nuspec metadata already defines aren't allowed.
+fn is_extra_metadata_name(name: &str) -> bool {
+    is_element_name(name)
+        && !name.to_lowercase().starts_with("xml")
+        && !RESERVED_METADATA_ELEMENTS.iter().any(|reserved| reserved.eq_ignore_ascii_case(name))
+}
+
+/// Check whether a name is a valid XML element name without a namespace prefix, like `buildDate`.
+fn is_element_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    let first_valid = chars
+        .next()
+        .map(|c| c.is_alphabetic() || c == '_')
+        .unwrap_or(false);
+
+    first_valid && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Write package types.
+///
+/// Nothing is written if there are no package types.
+fn format_package_types(package_types: &[String], writer: &mut xml::Writer) -> Result<(), xml::Error> {
+    if package_types.is_empty() {
+        return Ok(());
+    }
+
+    xml::elem(writer, "packageTypes", &[], |ref mut writer| {
+        for package_type in package_types {
+            let name_attr = xml::attr("name", package_type);
+
+            xml::elem(writer, "packageType", &[name_attr], |_| Ok(()))?;
+        }
+
+        Ok(())
+    })
 }
 
 /// Write package dependencies.
@@ -107,21 +476,112 @@ fn format_dependencies<'a>(
     })
 }
 
+/// Write framework references, grouped by target framework.
+///
+/// Nothing is written if there are no framework references.
+fn format_framework_references<'a>(
+    references: &[NugetFrameworkReference<'a>],
+    writer: &mut xml::Writer,
+) -> Result<(), xml::Error> {
+    if references.is_empty() {
+        return Ok(());
+    }
+
+    let mut frameworks: Vec<&str> = Vec::new();
+    for reference in references {
+        if !frameworks.contains(&reference.target_framework.as_ref()) {
+            frameworks.push(&reference.target_framework);
+        }
+    }
+
+    xml::elem(writer, "frameworkReferences", &[], |ref mut writer| {
+        for framework in &frameworks {
+            let tfm_attr = xml::attr("targetFramework", framework);
+
+            xml::elem(writer, "group", &[tfm_attr], |ref mut writer| {
+                let group = references
+                    .iter()
+                    .filter(|reference| reference.target_framework == *framework);
+
+                for reference in group {
+                    let name_attr = xml::attr("name", &reference.name);
+
+                    xml::elem(writer, "frameworkReference", &[name_attr], |_| Ok(()))?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Write files to include in the package.
+///
+/// Nothing is written if there are no files.
+fn format_files<'a>(files: &[NugetFile<'a>], writer: &mut xml::Writer) -> Result<(), xml::Error> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    xml::elem(writer, "files", &[], |ref mut writer| {
+        for file in files {
+            let src_attr = xml::attr("src", &file.src);
+            let target_attr = xml::attr("target", &file.target);
+
+            xml::elem(writer, "file", &[src_attr, target_attr], |_| Ok(()))?;
+        }
+
+        Ok(())
+    })
+}
+
 quick_error!{
     /// An error encountered formatting a Nuspec.
     #[derive(Debug)]
     pub enum NugetSpecError {
         /// An io-related error writing the nuspec.
         Io(err: IoError) {
-            cause(err)
+            source(err)
             display("Error writing nuget config\nCaused by: {}", err)
             from()
         }
         /// An xml formatting error.
         Xml(err: xml::Error) {
+            source(err)
             display("Error writing nuget config\nCaused by: {}", err)
             from()
         }
+        /// The minimum client version isn't a valid version.
+        InvalidMinClientVersion { version: String } {
+            display("The minimum client version '{}' isn't a valid version, like '3.3.0'", version)
+        }
+        /// A custom metadata element doesn't have a valid XML name.
+        InvalidMetadataElement { name: String } {
+            display("The metadata element name '{}' isn't a valid XML name, like 'buildDate', or is reserved by nuspec", name)
+        }
+        /// A dependency references a prerelease version, which isn't allowed.
+        PrereleaseDependency { id: String, version: String } {
+            display("The dependency '{}' references the prerelease version '{}'\nPrerelease dependencies need to be explicitly allowed", id, version)
+        }
+        /// The package type isn't one NuGet knows about.
+        InvalidPackageType { name: String } {
+            display("The package type '{}' isn't valid, expected an identifier like 'DotnetTool'", name)
+        }
+        /// The author separator isn't a punctuation character.
+        InvalidAuthorSeparator { separator: char } {
+            display("The author separator '{}' isn't valid, expected punctuation like ',' or ';'", separator)
+        }
+        /// A lib can't be listed as a file because its entry name isn't valid.
+        InvalidLibFile { path: String, err: NugetWriteLibError } {
+            source(err)
+            display("Error listing lib at path {}\nCaused by: {}", path, err)
+        }
+        /// The language isn't a valid language tag.
+        InvalidLanguage { language: String } {
+            display("The language '{}' isn't a valid language tag, like 'en-US'", language)
+        }
     }
 }
 
@@ -129,14 +589,47 @@ quick_error!{
 mod tests {
     use super::*;
 
+    fn args() -> NugetSpecArgs<'static> {
+        NugetSpecArgs {
+            id: "native".into(),
+            version: "0.1.0".into(),
+            authors: vec!["Someone".into()],
+            owners: vec![],
+            description: "A description for this package".into(),
+            repository: "https://github.com/KodrAus/cargo-nuget".into(),
+            project_url: None,
+            language: None,
+            serviceable: false,
+            min_client_version: None,
+            release_notes: None,
+            tags: vec![],
+            dependencies: NugetDependencies(vec![]),
+            framework_references: vec![],
+            extra_metadata: vec![],
+            max_description_len: DEFAULT_MAX_DESCRIPTION_LEN,
+            package_types: vec![],
+            allow_prerelease_deps: false,
+            files: vec![],
+            author_separator: DEFAULT_AUTHOR_SEPARATOR,
+            title: None,
+        }
+    }
+
     #[test]
     fn format_nuget() {
         let args = NugetSpecArgs {
             id: "native".into(),
             version: "0.1.0".into(),
-            authors: "Someone".into(),
+            authors: vec!["Someone".into()],
+            owners: vec![],
             description: "A description for this package".into(),
             repository: "https://github.com/KodrAus/cargo-nuget".into(),
+            project_url: None,
+            language: None,
+            serviceable: false,
+            min_client_version: None,
+            release_notes: None,
+            tags: vec![],
             dependencies: NugetDependencies(vec![
                 NugetDependency {
                     id: "A".into(),
@@ -148,6 +641,14 @@ mod tests {
                     version: "1.0.0-rc11".into(),
                 },
             ]),
+            framework_references: vec![],
+            extra_metadata: vec![],
+            max_description_len: DEFAULT_MAX_DESCRIPTION_LEN,
+            package_types: vec![],
+            allow_prerelease_deps: true,
+            files: vec![],
+            author_separator: DEFAULT_AUTHOR_SEPARATOR,
+            title: None,
         };
 
         let nuspec = spec(args).unwrap();
@@ -171,4 +672,700 @@ mod tests {
 
         assert_eq_no_ws!(expected, &nuspec.xml);
     }
+
+    #[test]
+    fn format_nuget_framework_references() {
+        let args = NugetSpecArgs {
+            id: "native".into(),
+            version: "0.1.0".into(),
+            authors: vec!["Someone".into()],
+            owners: vec![],
+            description: "A description for this package".into(),
+            repository: "https://github.com/KodrAus/cargo-nuget".into(),
+            project_url: None,
+            dependencies: NugetDependencies(vec![]),
+            framework_references: vec![
+                NugetFrameworkReference {
+                    target_framework: "netcoreapp3.0".into(),
+                    name: "Microsoft.AspNetCore.App".into(),
+                },
+                NugetFrameworkReference {
+                    target_framework: "netcoreapp3.0".into(),
+                    name: "Microsoft.WindowsDesktop.App".into(),
+                },
+            ],
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <dependencies />
+                    <frameworkReferences>
+                        <group targetFramework="netcoreapp3.0">
+                            <frameworkReference name="Microsoft.AspNetCore.App" />
+                            <frameworkReference name="Microsoft.WindowsDesktop.App" />
+                        </group>
+                    </frameworkReferences>
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_extra_metadata() {
+        let args = NugetSpecArgs {
+            extra_metadata: vec![
+                ("buildDate".into(), "2017-03-01".into()),
+                ("buildNotes".into(), "a < b & c".into()),
+            ],
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <buildDate>2017-03-01</buildDate>
+                    <buildNotes>a &lt; b &amp; c</buildNotes>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_invalid_extra_metadata() {
+        for name in &["", "1st", "some:name", "some name", "<evil>"] {
+            let args = NugetSpecArgs {
+                extra_metadata: vec![(name.to_string(), "value".into())],
+                ..args()
+            };
+
+            match spec(args) {
+                Err(NugetSpecError::InvalidMetadataElement { .. }) => (),
+                r => panic!("{:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn format_nuget_reserved_extra_metadata() {
+        for name in &["id", "version", "Authors", "dependencies", "xml", "xmlThing", "XMLThing"] {
+            let args = NugetSpecArgs {
+                extra_metadata: vec![(name.to_string(), "value".into())],
+                ..args()
+            };
+
+            match spec(args) {
+                Err(NugetSpecError::InvalidMetadataElement { name: ref n }) if n == name => (),
+                r => panic!("{:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn format_nuget_long_description() {
+        let description = "a".repeat(11);
+
+        let args = NugetSpecArgs {
+            description: description.into(),
+            max_description_len: 10,
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        assert_eq!(1, nuspec.warnings.len());
+    }
+
+    #[test]
+    fn format_nuget_description_within_limit() {
+        let args = NugetSpecArgs {
+            description: "a".repeat(10).into(),
+            max_description_len: 10,
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        assert!(nuspec.warnings.is_empty());
+    }
+
+    #[test]
+    fn format_nuget_package_types() {
+        let args = NugetSpecArgs {
+            package_types: vec!["DotnetTool".into()],
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <packageTypes>
+                        <packageType name="DotnetTool" />
+                    </packageTypes>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_custom_package_type() {
+        let args = NugetSpecArgs {
+            package_types: vec!["MyOrg.Plugin".into()],
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+        let xml = String::from_utf8(nuspec.xml.to_vec()).unwrap();
+
+        assert!(xml.contains(r#"<packageType name="MyOrg.Plugin" />"#));
+    }
+
+    #[test]
+    fn format_nuget_invalid_package_type() {
+        for name in &["", "1st", "Not A Type", "<evil>"] {
+            let args = NugetSpecArgs {
+                package_types: vec![name.to_string()],
+                ..args()
+            };
+
+            match spec(args) {
+                Err(NugetSpecError::InvalidPackageType { name: ref n }) if n == name => (),
+                r => panic!("{:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn format_nuget_prerelease_dependency() {
+        let args = NugetSpecArgs {
+            dependencies: NugetDependencies(vec![
+                NugetDependency {
+                    id: "Some.Dependency".into(),
+                    version: "[1.0.0-beta.1, 2.0.0)".into(),
+                },
+            ]),
+            ..args()
+        };
+
+        match spec(args) {
+            Err(NugetSpecError::PrereleaseDependency { ref id, .. }) if id == "Some.Dependency" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn format_nuget_prerelease_dependency_among_stable() {
+        let args = NugetSpecArgs {
+            dependencies: NugetDependencies(vec![
+                NugetDependency {
+                    id: "Stable.Dependency".into(),
+                    version: "1.0.0".into(),
+                },
+                NugetDependency {
+                    id: "Prerelease.Dependency".into(),
+                    version: "1.0.0-rc11".into(),
+                },
+            ]),
+            ..args()
+        };
+
+        match spec(args) {
+            Err(NugetSpecError::PrereleaseDependency { ref id, ref version })
+                if id == "Prerelease.Dependency" && version == "1.0.0-rc11" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn format_nuget_allow_prerelease_dependency() {
+        let args = NugetSpecArgs {
+            dependencies: NugetDependencies(vec![
+                NugetDependency {
+                    id: "Some.Dependency".into(),
+                    version: "[1.0.0-beta.1, 2.0.0)".into(),
+                },
+            ]),
+            allow_prerelease_deps: true,
+            ..args()
+        };
+
+        assert!(spec(args).is_ok());
+    }
+
+    #[test]
+    fn prerelease_ranges() {
+        assert!(is_prerelease_range("1.0.0-rc.1"));
+        assert!(is_prerelease_range("(, 2.0.0-alpha]"));
+        assert!(!is_prerelease_range("[1.0.1, )"));
+        assert!(!is_prerelease_range("1.0.0+build-5"));
+    }
+
+    #[test]
+    fn format_nuget_project_url() {
+        let args = NugetSpecArgs {
+            project_url: Some("https://kodraus.github.io".into()),
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <projectUrl>https://kodraus.github.io</projectUrl>
+                    <description>A description for this package</description>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_language() {
+        let args = NugetSpecArgs {
+            language: Some("en-US".into()),
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <language>en-US</language>
+                    <description>A description for this package</description>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_no_language() {
+        let nuspec = spec(args()).unwrap();
+
+        let xml = String::from_utf8(nuspec.xml.to_vec()).unwrap();
+
+        assert!(!xml.contains("<language>"));
+    }
+
+    #[test]
+    fn format_nuget_invalid_language() {
+        let args = NugetSpecArgs {
+            language: Some("not a language".into()),
+            ..args()
+        };
+
+        match spec(args) {
+            Err(NugetSpecError::InvalidLanguage { .. }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn format_nuget_serviceable() {
+        let args = NugetSpecArgs {
+            serviceable: true,
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <serviceable>true</serviceable>
+                    <description>A description for this package</description>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_not_serviceable() {
+        let nuspec = spec(args()).unwrap();
+
+        let xml = String::from_utf8(nuspec.xml.to_vec()).unwrap();
+
+        assert!(!xml.contains("<serviceable>"));
+    }
+
+    #[test]
+    fn format_nuget_min_client_version() {
+        let args = NugetSpecArgs {
+            min_client_version: Some("3.3.0".into()),
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata minClientVersion="3.3.0">
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_no_min_client_version() {
+        let nuspec = spec(args()).unwrap();
+
+        let xml = String::from_utf8(nuspec.xml.to_vec()).unwrap();
+
+        assert!(!xml.contains("minClientVersion"));
+    }
+
+    #[test]
+    fn format_nuget_invalid_min_client_version() {
+        let args = NugetSpecArgs {
+            min_client_version: Some("three".into()),
+            ..args()
+        };
+
+        match spec(args) {
+            Err(NugetSpecError::InvalidMinClientVersion { .. }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn format_nuget_owners() {
+        let args = NugetSpecArgs {
+            owners: vec!["Someone".into(), "Someone Else".into()],
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <owners>Someone, Someone Else</owners>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_release_notes() {
+        let args = NugetSpecArgs {
+            release_notes: Some("- Fixed <things & stuff".into()),
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <releaseNotes>- Fixed &lt;things &amp; stuff</releaseNotes>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_no_release_notes() {
+        let nuspec = spec(args()).unwrap();
+
+        let xml = String::from_utf8(nuspec.xml.to_vec()).unwrap();
+
+        assert!(!xml.contains("releaseNotes"));
+    }
+
+    #[test]
+    fn merge_tags_ignores_case() {
+        let tags = merge_tags(vec!["Rust", "rust", "ffi"]);
+
+        assert_eq!(vec!["Rust", "ffi"], tags);
+    }
+
+    #[test]
+    fn format_nuget_tags() {
+        let args = NugetSpecArgs {
+            tags: merge_tags(vec!["Rust", "rust", "ffi"]),
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <tags>Rust ffi</tags>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_lib_files() {
+        use std::path::Path;
+        use args::{Arch, CrossTarget};
+        use nuget::NugetLibs;
+
+        let libs: NugetLibs = vec![
+            (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("target/release/native.dll")),
+            (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("target/release/libnative.so")),
+            (Target::Unknown, Path::new("target/release/libnative.a")),
+        ].into_iter().collect();
+
+        let args = args().with_lib_files(&NugetLibLayout::Native, &libs).unwrap();
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <dependencies />
+                </metadata>
+                <files>
+                    <file src="target/release/libnative.so" target="runtimes/linux-x64/native/native.so" />
+                    <file src="target/release/native.dll" target="runtimes/win-x64/native/native.dll" />
+                </files>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_lib_files_rid_override() {
+        use std::path::Path;
+        use args::{Arch, CrossTarget};
+
+        let mut libs = HashMap::new();
+        libs.insert(
+            Target::Cross(CrossTarget::Linux(Arch::x64)),
+            NugetLib::new(Path::new("target/release/libnative.so")).with_rid("ubuntu.22.04-x64").unwrap(),
+        );
+
+        let files = args().with_lib_files(&NugetLibLayout::Native, &libs).unwrap().files;
+
+        assert_eq!("runtimes/ubuntu.22.04-x64/native/native.so", files[0].target);
+
+        let args = NugetSpecArgs {
+            id: "../evil".into(),
+            ..args()
+        };
+
+        match args.with_lib_files(&NugetLibLayout::Native, &libs) {
+            Err(NugetSpecError::InvalidLibFile { .. }) => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn join_authors_with_separator() {
+        let authors = ["Someone", "Someone Else"];
+
+        assert_eq!("Someone, Someone Else", join_authors(&authors, ','));
+        assert_eq!("Someone; Someone Else", join_authors(&authors, ';'));
+    }
+
+    #[test]
+    fn format_nuget_author_separator() {
+        let args = NugetSpecArgs {
+            authors: vec!["Someone".into(), "Someone Else".into()],
+            owners: vec!["An Owner".into(), "Another Owner".into()],
+            author_separator: ';',
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        assert_eq!("Someone; Someone Else", nuspec.authors);
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <authors>Someone; Someone Else</authors>
+                    <owners>An Owner; Another Owner</owners>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_invalid_author_separator() {
+        for &separator in &['a', ' ', '<'] {
+            let args = NugetSpecArgs {
+                author_separator: separator,
+                ..args()
+            };
+
+            match spec(args) {
+                Err(NugetSpecError::InvalidAuthorSeparator { separator: s }) if s == separator => (),
+                r => panic!("{:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn format_nuget_many_authors() {
+        let authors: Vec<_> = (0..10000).map(|i| format!("Author {}", i)).collect();
+        let tags: Vec<_> = (0..10000).map(|i| format!("tag{}", i)).collect();
+
+        let joined = join_authors(&authors, ',');
+
+        assert!(joined.starts_with("Author 0, Author 1, "));
+        assert!(joined.ends_with(", Author 9999"));
+        assert_eq!(authors.len(), joined.split(", ").count());
+
+        let args = NugetSpecArgs {
+            authors: authors.iter().map(|author| author.as_str().into()).collect(),
+            tags: merge_tags(tags.iter().chain(&tags).map(|tag| tag.as_str())),
+            ..args()
+        };
+
+        assert_eq!(10000, args.tags.len());
+
+        let nuspec = spec(args).unwrap();
+        let xml = String::from_utf8(nuspec.xml.to_vec()).unwrap();
+
+        assert!(xml.contains("Author 5000, Author 5001"));
+        assert!(xml.contains("tag9998 tag9999"));
+    }
+
+    #[test]
+    fn format_nuget_title() {
+        let args = NugetSpecArgs {
+            title: Some("Native Bindings".into()),
+            ..args()
+        };
+
+        let nuspec = spec(args).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <package xmlns="http://schemas.microsoft.com/packaging/2012/06/nuspec.xsd">
+                <metadata>
+                    <id>native</id>
+                    <version>0.1.0</version>
+                    <title>Native Bindings</title>
+                    <authors>Someone</authors>
+                    <repository url="https://github.com/KodrAus/cargo-nuget" />
+                    <description>A description for this package</description>
+                    <dependencies />
+                </metadata>
+            </package>
+        "#;
+
+        assert_eq_no_ws!(expected, &nuspec.xml);
+    }
+
+    #[test]
+    fn format_nuget_no_title() {
+        let nuspec = spec(args()).unwrap();
+
+        let xml = String::from_utf8(nuspec.xml.to_vec()).unwrap();
+
+        assert!(!xml.contains("<title>"));
+    }
 }
@@ -0,0 +1,133 @@
+//! Extract the contents of a `nupkg`.
+
+use std::fs::{self, File};
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use super::{Buf, NugetPackError};
+
+/// Extract every entry in a `nupkg` to files under `dest`.
+///
+/// Entries are streamed to disk one at a time and the paths of extracted files are returned.
+/// Entries with paths that could escape `dest`, like `../evil` or `/evil`, are rejected.
+pub fn unpack(buf: &Buf, dest: &Path) -> Result<Vec<PathBuf>, NugetPackError> {
+    let mut archive = ZipArchive::new(Cursor::new(&buf[..]))?;
+
+    let mut paths = Vec::with_capacity(archive.len());
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().replace("\\", "/");
+
+        let path = dest.join(entry_path(&name)?);
+
+        if name.ends_with('/') {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        io::copy(&mut entry, &mut File::create(&path)?)?;
+
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// Get the relative path to extract an entry to.
+///
+/// The name must already have its separators normalized to `/`.
+fn entry_path(name: &str) -> Result<PathBuf, NugetPackError> {
+    let unsafe_entry = || NugetPackError::UnsafeEntry {
+        name: name.to_owned(),
+    };
+
+    if name.starts_with('/') {
+        return Err(unsafe_entry());
+    }
+
+    let mut path = PathBuf::new();
+
+    for component in name.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if component == ".." || component.contains(':') {
+            return Err(unsafe_entry());
+        }
+
+        path.push(component);
+    }
+
+    if path.as_os_str().is_empty() {
+        return Err(unsafe_entry());
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    use args::{Arch, CrossTarget, Target};
+    use nuget::{pack, NugetPackArgs};
+    use tempdir::TempDir;
+    use super::*;
+
+    #[test]
+    fn pack_then_unpack() {
+        let spec = b"<package />".to_vec().into();
+        let args = NugetPackArgs::new("some_pkg", "0.1.0", "Someone", "A description for this package", &spec)
+            .with_libs(vec![
+                (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            ]);
+
+        let nupkg = pack(args).unwrap();
+
+        let dir = TempDir::new("pack-then-unpack");
+        let dest = dir.join("unpacked");
+
+        let paths = unpack(&nupkg.buf, &dest).unwrap();
+
+        let nuspec_path = dest.join("some_pkg.nuspec");
+        assert!(paths.contains(&nuspec_path));
+        assert!(paths.contains(&dest.join("runtimes/linux-x64/native/some_pkg.toml")));
+
+        let mut nuspec = Vec::new();
+        File::open(&nuspec_path).unwrap().read_to_end(&mut nuspec).unwrap();
+
+        assert_eq!(&spec[..], &nuspec[..]);
+    }
+
+    #[test]
+    fn unpack_rejects_zip_slip() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        writer.start_file("../evil.txt", FileOptions::default()).unwrap();
+        writer.write_all(b"evil").unwrap();
+
+        let buf: Buf = writer.finish().unwrap().into_inner().into();
+
+        let dir = TempDir::new("unpack-zip-slip");
+        let dest = dir.join("unpacked");
+
+        match unpack(&buf, &dest) {
+            Err(NugetPackError::UnsafeEntry { ref name }) if name == "../evil.txt" => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn entry_paths() {
+        assert_eq!(PathBuf::from("lib").join("some_pkg.dll"), entry_path("lib/./some_pkg.dll").unwrap());
+
+        assert!(entry_path("/evil.txt").is_err());
+        assert!(entry_path("lib/../../evil.txt").is_err());
+        assert!(entry_path("C:/evil.txt").is_err());
+        assert!(entry_path("").is_err());
+    }
+}
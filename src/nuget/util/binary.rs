@@ -0,0 +1,225 @@
+//! Inspect the headers of native binaries.
+
+use args::Arch;
+#[cfg(test)]
+use args::{CrossTarget, Target};
+
+const ELF_MAGIC: &'static [u8] = b"\x7fELF";
+const ELF_MACHINE_X86: u16 = 3;
+const ELF_MACHINE_X64: u16 = 62;
+const ELF_MACHINE_ARM64: u16 = 183;
+
+const MACHO_MAGIC: u32 = 0xfeed_face;
+const MACHO_MAGIC_64: u32 = 0xfeed_facf;
+const MACHO_CPU_X86: u32 = 0x0000_0007;
+const MACHO_CPU_X64: u32 = 0x0100_0007;
+const MACHO_CPU_ARM64: u32 = 0x0100_000c;
+
+const PE_MAGIC: &'static [u8] = b"PE\0\0";
+const PE_MACHINE_X86: u16 = 0x014c;
+const PE_MACHINE_X64: u16 = 0x8664;
+const PE_MACHINE_ARM64: u16 = 0xaa64;
+
+/// Detect the architecture a native binary was built for from its header.
+///
+/// ELF, Mach-O and PE headers are supported.
+/// `None` is returned if the format or architecture isn't recognised.
+pub fn arch(bytes: &[u8]) -> Option<Arch> {
+    elf_arch(bytes)
+        .or_else(|| macho_arch(bytes))
+        .or_else(|| pe_arch(bytes))
+}
+
+/// Read the `e_machine` field of an ELF header.
+fn elf_arch(bytes: &[u8]) -> Option<Arch> {
+    if bytes.len() < 20 || &bytes[..4] != ELF_MAGIC {
+        return None;
+    }
+
+    // The fifth byte says whether the header is little or big endian
+    let machine = match bytes[5] {
+        1 => u16_le(bytes, 18),
+        2 => u16_be(bytes, 18),
+        _ => return None,
+    };
+
+    match machine {
+        ELF_MACHINE_X86 => Some(Arch::x86),
+        ELF_MACHINE_X64 => Some(Arch::x64),
+        ELF_MACHINE_ARM64 => Some(Arch::arm64),
+        _ => None,
+    }
+}
+
+/// Read the `cputype` field of a Mach-O header.
+fn macho_arch(bytes: &[u8]) -> Option<Arch> {
+    if bytes.len() < 8 {
+        return None;
+    }
+
+    let cputype = match (u32_le(bytes, 0), u32_be(bytes, 0)) {
+        (MACHO_MAGIC, _) | (MACHO_MAGIC_64, _) => u32_le(bytes, 4),
+        (_, MACHO_MAGIC) | (_, MACHO_MAGIC_64) => u32_be(bytes, 4),
+        _ => return None,
+    };
+
+    match cputype {
+        MACHO_CPU_X86 => Some(Arch::x86),
+        MACHO_CPU_X64 => Some(Arch::x64),
+        MACHO_CPU_ARM64 => Some(Arch::arm64),
+        _ => None,
+    }
+}
+
+/// Read the `Machine` field of a PE header.
+fn pe_arch(bytes: &[u8]) -> Option<Arch> {
+    if bytes.len() < 0x40 || &bytes[..2] != b"MZ" {
+        return None;
+    }
+
+    // The offset to the PE header is stored at the end of the DOS header
+    let offset = u32_le(bytes, 0x3c) as usize;
+
+    match offset.checked_add(6) {
+        Some(end) if end <= bytes.len() => (),
+        _ => return None,
+    }
+
+    if &bytes[offset..offset + 4] != PE_MAGIC {
+        return None;
+    }
+
+    match u16_le(bytes, offset + 4) {
+        PE_MACHINE_X86 => Some(Arch::x86),
+        PE_MACHINE_X64 => Some(Arch::x64),
+        PE_MACHINE_ARM64 => Some(Arch::arm64),
+        _ => None,
+    }
+}
+
+fn u16_le(bytes: &[u8], at: usize) -> u16 {
+    (bytes[at] as u16) | ((bytes[at + 1] as u16) << 8)
+}
+
+fn u16_be(bytes: &[u8], at: usize) -> u16 {
+    ((bytes[at] as u16) << 8) | (bytes[at + 1] as u16)
+}
+
+fn u32_le(bytes: &[u8], at: usize) -> u32 {
+    (u16_le(bytes, at) as u32) | ((u16_le(bytes, at + 2) as u32) << 16)
+}
+
+fn u32_be(bytes: &[u8], at: usize) -> u32 {
+    ((u16_be(bytes, at) as u32) << 16) | (u16_be(bytes, at + 2) as u32)
+}
+
+/// Build a fake lib for a target, with just enough of a header for its architecture to be detected.
+///
+/// Linux and FreeBSD libs get an ELF header, MacOS libs a Mach-O header and Windows libs a PE header.
+#[cfg(test)]
+pub fn fake_lib(target: Target) -> Vec<u8> {
+    let target = target.cross().expect("fake libs need a known target");
+
+    match target {
+        CrossTarget::Linux(arch) | CrossTarget::FreeBSD(arch) => elf(match arch {
+            Arch::x86 => ELF_MACHINE_X86,
+            Arch::x64 => ELF_MACHINE_X64,
+            Arch::arm64 => ELF_MACHINE_ARM64,
+        }),
+        CrossTarget::MacOS(arch) => macho(match arch {
+            Arch::x86 => MACHO_CPU_X86,
+            Arch::x64 => MACHO_CPU_X64,
+            Arch::arm64 => MACHO_CPU_ARM64,
+        }),
+        CrossTarget::Windows(arch) => pe(match arch {
+            Arch::x86 => PE_MACHINE_X86,
+            Arch::x64 => PE_MACHINE_X64,
+            Arch::arm64 => PE_MACHINE_ARM64,
+        }),
+    }
+}
+
+#[cfg(test)]
+fn elf(machine: u16) -> Vec<u8> {
+    let mut bytes = vec![0; 64];
+    bytes[..4].copy_from_slice(ELF_MAGIC);
+    bytes[4] = 2;
+    bytes[5] = 1;
+    bytes[18] = machine as u8;
+    bytes[19] = (machine >> 8) as u8;
+
+    bytes
+}
+
+#[cfg(test)]
+fn macho(cputype: u32) -> Vec<u8> {
+    let mut bytes = vec![0; 32];
+    for i in 0..4 {
+        bytes[i] = (MACHO_MAGIC_64 >> (i * 8)) as u8;
+        bytes[4 + i] = (cputype >> (i * 8)) as u8;
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+fn pe(machine: u16) -> Vec<u8> {
+    let mut bytes = vec![0; 0x80];
+    bytes[..2].copy_from_slice(b"MZ");
+    bytes[0x3c] = 0x40;
+    bytes[0x40..0x44].copy_from_slice(PE_MAGIC);
+    bytes[0x44] = machine as u8;
+    bytes[0x45] = (machine >> 8) as u8;
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elf_header() {
+        assert_eq!(Some(Arch::x86), arch(&elf(ELF_MACHINE_X86)));
+        assert_eq!(Some(Arch::x64), arch(&elf(ELF_MACHINE_X64)));
+        assert_eq!(Some(Arch::arm64), arch(&elf(ELF_MACHINE_ARM64)));
+    }
+
+    #[test]
+    fn elf_header_big_endian() {
+        let mut bytes = elf(0);
+        bytes[5] = 2;
+        bytes[19] = ELF_MACHINE_X64 as u8;
+
+        assert_eq!(Some(Arch::x64), arch(&bytes));
+    }
+
+    #[test]
+    fn macho_header() {
+        assert_eq!(Some(Arch::x86), arch(&macho(MACHO_CPU_X86)));
+        assert_eq!(Some(Arch::x64), arch(&macho(MACHO_CPU_X64)));
+        assert_eq!(Some(Arch::arm64), arch(&macho(MACHO_CPU_ARM64)));
+    }
+
+    #[test]
+    fn pe_header() {
+        assert_eq!(Some(Arch::x86), arch(&pe(PE_MACHINE_X86)));
+        assert_eq!(Some(Arch::x64), arch(&pe(PE_MACHINE_X64)));
+        assert_eq!(Some(Arch::arm64), arch(&pe(PE_MACHINE_ARM64)));
+    }
+
+    #[test]
+    fn fake_lib_headers() {
+        for &arch in &[Arch::x86, Arch::x64, Arch::arm64] {
+            for &target in &[CrossTarget::Linux(arch), CrossTarget::MacOS(arch), CrossTarget::Windows(arch)] {
+                assert_eq!(Some(arch), super::arch(&fake_lib(Target::Cross(target))));
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_header() {
+        assert_eq!(None, arch(b"not a binary"));
+        assert_eq!(None, arch(&elf(0xffff)));
+    }
+}
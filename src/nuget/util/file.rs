@@ -0,0 +1,38 @@
+//! Write files without leaving partial ones behind.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+
+/// Write a file by writing to a temporary file alongside it, then renaming it into place.
+///
+/// If writing fails then the temporary file is removed, so a partial file is never left at `path`.
+pub fn write_with<F>(path: &Path, write: F) -> Result<(), IoError>
+where
+    F: FnOnce(&mut File) -> Result<(), IoError>,
+{
+    let tmp_path = tmp_path(path);
+
+    let written = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open(&tmp_path)
+        .and_then(|mut f| write(&mut f).and_then(|_| f.sync_all()))
+        .and_then(|_| fs::rename(&tmp_path, path));
+
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    written
+}
+
+/// Get the path of the temporary file to write before renaming it to `path`.
+pub fn tmp_path(path: &Path) -> PathBuf {
+    let name = path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.tmp", name))
+}
@@ -1,2 +1,5 @@
 pub mod xml;
 pub mod openxml;
+pub mod binary;
+pub mod strip;
+pub mod file;
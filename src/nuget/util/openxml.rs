@@ -1,10 +1,45 @@
 //! OpenXML specific files.
 
 use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
 
 use super::xml;
 
-pub fn content_types() -> Result<(PathBuf, Vec<u8>), xml::Error> {
+const PNG_MAGIC: &'static [u8] = b"\x89PNG\r\n\x1a\n";
+const XML_MAGIC: &'static [u8] = b"<?xml";
+const UTF8_BOM: &'static [u8] = b"\xef\xbb\xbf";
+
+/// Detect the content type of a file from its magic bytes.
+///
+/// If the bytes aren't recognised then the extension is used.
+/// Anything else falls back to `application/octet-stream`.
+pub fn detect_content_type(bytes: &[u8], ext: Option<&str>) -> &'static str {
+    let text = if bytes.starts_with(UTF8_BOM) {
+        &bytes[UTF8_BOM.len()..]
+    } else {
+        bytes
+    };
+
+    if bytes.starts_with(PNG_MAGIC) {
+        return "image/png";
+    }
+
+    if text.starts_with(XML_MAGIC) {
+        return "application/xml";
+    }
+
+    match ext.map(|ext| ext.to_lowercase()).as_ref().map(|ext| ext.as_str()) {
+        Some("png") => "image/png",
+        Some("xml") => "application/xml",
+        Some("txt") | Some("md") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Format the `[Content_Types].xml` file.
+///
+/// Extra extensions are registered after the defaults, ignoring any that are already known.
+pub fn content_types(extra: &[(String, &'static str)]) -> Result<(PathBuf, Vec<u8>), xml::Error> {
     let mut writer = xml::writer()?;
 
     let ns = xml::attr(
@@ -34,12 +69,29 @@ pub fn content_types() -> Result<(PathBuf, Vec<u8>), xml::Error> {
             ("dylib", "application/octet"),
             ("so", "application/octet"),
             ("nuspec", "application/octet"),
+            ("h", "application/octet"),
+            ("json", "application/octet"),
+            (
+                "psmdcp",
+                "application/vnd.openxmlformats-package.core-properties+xml",
+            ),
         ];
 
         for &(extension, content_type) in &types {
             default(writer, extension, content_type)?;
         }
 
+        let mut known: Vec<_> = types.iter().map(|&(extension, _)| extension).collect();
+
+        for &(ref extension, content_type) in extra {
+            if known.iter().any(|k| k.eq_ignore_ascii_case(extension)) {
+                continue;
+            }
+
+            default(writer, extension, content_type)?;
+            known.push(extension);
+        }
+
         Ok(())
     })?;
 
@@ -50,11 +102,18 @@ pub fn content_types() -> Result<(PathBuf, Vec<u8>), xml::Error> {
     Ok((path, writer.into_inner()))
 }
 
-pub fn relationships<P>(nuspec_path: P) -> Result<(PathBuf, Vec<u8>), xml::Error>
+/// Format the `_rels/.rels` file.
+///
+/// The package relates to its `nuspec` manifest and its core properties.
+/// Each relationship has an `Id`, which strict OPC consumers require.
+/// Ids are derived from their target so they're stable between packs.
+pub fn relationships<P, Q>(nuspec_path: P, core_properties_path: Q) -> Result<(PathBuf, Vec<u8>), xml::Error>
 where
     P: AsRef<Path>,
+    Q: AsRef<Path>,
 {
     let nuspec_path = nuspec_path.as_ref();
+    let core_properties_path = core_properties_path.as_ref();
 
     let mut writer = xml::writer()?;
 
@@ -64,15 +123,35 @@ where
     );
 
     xml::elem(&mut writer, "Relationships", &[ns], |ref mut writer| {
-        let ty = xml::attr(
-            "Type",
-            "http://schemas.microsoft.com/packaging/2010/07/manifest",
-        );
+        fn relationship(writer: &mut xml::Writer, ty: &str, target: &Path) -> Result<(), xml::Error> {
+            let ty = xml::attr("Type", ty);
+
+            let target = format!("/{}", target.to_string_lossy().replace("\\", "/"));
+
+            let id: String = Sha256::digest(target.as_bytes())
+                .iter()
+                .take(8)
+                .map(|b| format!("{:02X}", b))
+                .collect();
+            let id = format!("R{}", id);
 
-        let target = format!("/{}", nuspec_path.to_string_lossy());
-        let target = xml::attr("Target", &target);
+            let target = xml::attr("Target", &target);
+            let id = xml::attr("Id", &id);
 
-        xml::elem(writer, "Relationship", &[ty, target], |_| Ok(()))
+            xml::elem(writer, "Relationship", &[ty, target, id], |_| Ok(()))
+        }
+
+        relationship(
+            writer,
+            "http://schemas.microsoft.com/packaging/2010/07/manifest",
+            nuspec_path,
+        )?;
+
+        relationship(
+            writer,
+            "http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties",
+            core_properties_path,
+        )
     })?;
 
     let mut path = PathBuf::new();
@@ -82,6 +161,53 @@ where
     Ok((path, writer.into_inner()))
 }
 
+/// Format the `package/services/metadata/core-properties/{id}.psmdcp` file.
+///
+/// The file name is derived from the package id and version, so packing the
+/// same package twice produces the same path.
+pub fn core_properties(
+    id: &str,
+    version: &str,
+    authors: &str,
+    description: &str,
+) -> Result<(PathBuf, Vec<u8>), xml::Error> {
+    let mut writer = xml::writer()?;
+
+    let attrs = [
+        xml::attr("xmlns:dc", "http://purl.org/dc/elements/1.1/"),
+        xml::attr("xmlns:dcterms", "http://purl.org/dc/terms/"),
+        xml::attr("xmlns:xsi", "http://www.w3.org/2001/XMLSchema-instance"),
+        xml::attr(
+            "xmlns",
+            "http://schemas.openxmlformats.org/package/2006/metadata/core-properties",
+        ),
+    ];
+
+    xml::elem(&mut writer, "coreProperties", &attrs, |ref mut writer| {
+        xml::val(writer, "dc:creator", &authors)?;
+        xml::val(writer, "dc:description", &description)?;
+        xml::val(writer, "dc:identifier", &id)?;
+        xml::val(writer, "version", &version)?;
+        xml::val(writer, "lastModifiedBy", &"cargo-nuget")
+    })?;
+
+    let name: String = Sha256::digest(format!("{}.{}", id, version).as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    let mut path = PathBuf::new();
+    path.push("package");
+    path.push("services");
+    path.push("metadata");
+    path.push("core-properties");
+    path.push(name);
+    path.set_extension("psmdcp");
+
+    Ok((path, writer.into_inner()))
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -89,12 +215,13 @@ mod tests {
 
     #[test]
     fn rels_file() {
-        let (path, content) = relationships("some/path/spec.nuspec").unwrap();
+        let (path, content) = relationships("some/path/spec.nuspec", "some/path/props.psmdcp").unwrap();
 
         let expected = br#"
             <?xml version="1.0" encoding="UTF-8"?>
             <Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
-                <Relationship Type="http://schemas.microsoft.com/packaging/2010/07/manifest" Target="/some/path/spec.nuspec" />
+                <Relationship Type="http://schemas.microsoft.com/packaging/2010/07/manifest" Target="/some/path/spec.nuspec" Id="R6A544D75DFBABDF6" />
+                <Relationship Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="/some/path/props.psmdcp" Id="R118A65D99F4493B4" />
             </Relationships>
         "#;
 
@@ -104,7 +231,7 @@ mod tests {
 
     #[test]
     fn content_types_file() {
-        let (path, content) = content_types().unwrap();
+        let (path, content) = content_types(&[]).unwrap();
 
         let expected = br#"
             <?xml version="1.0" encoding="UTF-8"?>
@@ -115,10 +242,86 @@ mod tests {
                 <Default Extension="dylib" ContentType="application/octet" />
                 <Default Extension="so" ContentType="application/octet" />
                 <Default Extension="nuspec" ContentType="application/octet" />
+                <Default Extension="h" ContentType="application/octet" />
+                <Default Extension="json" ContentType="application/octet" />
+                <Default Extension="psmdcp" ContentType="application/vnd.openxmlformats-package.core-properties+xml" />
             </Types>
         "#;
 
         assert_eq!(PathBuf::from("[Content_Types].xml"), path);
         assert_eq_no_ws!(expected, &content);
     }
+
+    #[test]
+    fn content_types_file_extra() {
+        let extra = vec![
+            ("png".to_owned(), "image/png"),
+            ("so".to_owned(), "application/octet-stream"),
+            ("PNG".to_owned(), "image/png"),
+        ];
+
+        let (_, content) = content_types(&extra).unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+                <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml" />
+                <Default Extension="txt" ContentType="application/octet" />
+                <Default Extension="dll" ContentType="application/octet" />
+                <Default Extension="dylib" ContentType="application/octet" />
+                <Default Extension="so" ContentType="application/octet" />
+                <Default Extension="nuspec" ContentType="application/octet" />
+                <Default Extension="h" ContentType="application/octet" />
+                <Default Extension="json" ContentType="application/octet" />
+                <Default Extension="psmdcp" ContentType="application/vnd.openxmlformats-package.core-properties+xml" />
+                <Default Extension="png" ContentType="image/png" />
+            </Types>
+        "#;
+
+        assert_eq_no_ws!(expected, &content);
+    }
+
+    #[test]
+    fn detect_png() {
+        let bytes = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+        assert_eq!("image/png", detect_content_type(bytes, None));
+        assert_eq!("image/png", detect_content_type(bytes, Some("bin")));
+    }
+
+    #[test]
+    fn detect_xml() {
+        assert_eq!("application/xml", detect_content_type(b"<?xml version=\"1.0\"?><a />", None));
+        assert_eq!("application/xml", detect_content_type(b"\xef\xbb\xbf<?xml version=\"1.0\"?>", None));
+        assert_eq!("application/xml", detect_content_type(b"<a />", Some("XML")));
+    }
+
+    #[test]
+    fn detect_unknown() {
+        assert_eq!("application/octet-stream", detect_content_type(b"\x7fELF", None));
+        assert_eq!("application/octet-stream", detect_content_type(b"", Some("bin")));
+    }
+
+    #[test]
+    fn core_properties_file() {
+        let (path, content) = core_properties("some_pkg", "0.1.0", "Someone", "A description").unwrap();
+
+        let expected = br#"
+            <?xml version="1.0" encoding="UTF-8"?>
+            <coreProperties xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns="http://schemas.openxmlformats.org/package/2006/metadata/core-properties">
+                <dc:creator>Someone</dc:creator>
+                <dc:description>A description</dc:description>
+                <dc:identifier>some_pkg</dc:identifier>
+                <version>0.1.0</version>
+                <lastModifiedBy>cargo-nuget</lastModifiedBy>
+            </coreProperties>
+        "#;
+
+        assert_eq!(Some("psmdcp".as_ref()), path.extension());
+        assert!(path.starts_with("package/services/metadata/core-properties"));
+        assert_eq_no_ws!(expected, &content);
+
+        let (same_path, _) = core_properties("some_pkg", "0.1.0", "", "").unwrap();
+        assert_eq!(path, same_path);
+    }
 }
@@ -0,0 +1,95 @@
+//! Strip debug sections from native binaries.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::process::{self, Command, Stdio};
+
+use args::Target;
+
+/// Strip debug sections from a native binary using the `strip` tool.
+///
+/// Only ELF and Mach-O binaries are stripped; Windows debug info lives in a separate `pdb`.
+/// `None` is returned if stripping doesn't apply to the target or the `strip` tool
+/// isn't available, in which case the original binary should be used.
+pub fn strip_debug(target: &Target, name: &str, lib: &[u8]) -> Option<Vec<u8>> {
+    let flag = if target.is_macos() {
+        "-S"
+    } else if target.is_unix() {
+        "--strip-debug"
+    } else {
+        return None;
+    };
+
+    let path = env::temp_dir().join(format!("cargo-nuget-strip-{}-{}", process::id(), name));
+
+    let strip = || -> Option<Vec<u8>> {
+        File::create(&path).and_then(|mut f| f.write_all(lib)).ok()?;
+
+        let status = Command::new("strip")
+            .arg(flag)
+            .arg(&path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .ok()?;
+
+        if !status.success() {
+            warn!("'strip' could not strip debug sections from '{}'", name);
+            return None;
+        }
+
+        let mut buf = Vec::new();
+        File::open(&path).and_then(|mut f| f.read_to_end(&mut buf)).ok()?;
+
+        Some(buf)
+    };
+
+    let stripped = strip();
+
+    let _ = fs::remove_file(&path);
+
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::Read;
+
+    use args::{Arch, CrossTarget, Target};
+    use super::super::binary;
+    use super::*;
+
+    #[test]
+    fn strip_windows_is_noop() {
+        let target = Target::Cross(CrossTarget::Windows(Arch::x64));
+
+        assert_eq!(None, strip_debug(&target, "native.dll", b"MZ"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn strip_test_binary() {
+        let mut lib = Vec::new();
+        File::open(env::current_exe().unwrap())
+            .unwrap()
+            .read_to_end(&mut lib)
+            .unwrap();
+
+        let target = match CrossTarget::local() {
+            Some(target) => Target::Cross(target),
+            None => return,
+        };
+
+        // The `strip` tool isn't available everywhere
+        let stripped = match strip_debug(&target, "cargo-nuget-test", &lib) {
+            Some(stripped) => stripped,
+            None => return,
+        };
+
+        assert!(stripped.len() < lib.len());
+        assert_eq!(binary::arch(&lib), binary::arch(&stripped));
+    }
+}
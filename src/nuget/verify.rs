@@ -0,0 +1,294 @@
+//! Check the structure of a `nupkg`.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+use zip::result::ZipError;
+use xml::reader::{EventReader, XmlEvent};
+
+use super::{Buf, NugetPackError};
+
+const RELS_PATH: &'static str = "_rels/.rels";
+const CONTENT_TYPES_PATH: &'static str = "[Content_Types].xml";
+
+/// The entry NuGet writes a package signature to.
+pub const SIGNATURE_PATH: &'static str = ".signature.p7s";
+
+/// The problems found in a `nupkg`.
+#[derive(Debug, Default, PartialEq)]
+pub struct VerifyReport {
+    pub problems: Vec<VerifyProblem>,
+}
+
+impl VerifyReport {
+    /// Whether the package is well-formed.
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// A single problem with the structure of a `nupkg`.
+#[derive(Debug, PartialEq)]
+pub enum VerifyProblem {
+    /// There's no `.nuspec` in the root of the package.
+    MissingNuspec,
+    /// There's more than one `.nuspec` in the root of the package.
+    MultipleNuspecs { paths: Vec<String> },
+    /// There's no `_rels/.rels`.
+    MissingRels,
+    /// There's no `[Content_Types].xml`.
+    MissingContentTypes,
+    /// The `[Content_Types].xml` couldn't be read.
+    BadContentTypes,
+    /// A runtime lib has an extension without a registered content type.
+    UnregisteredContentType { path: String },
+}
+
+impl fmt::Display for VerifyProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerifyProblem::MissingNuspec => write!(f, "The package doesn't contain a nuspec"),
+            VerifyProblem::MultipleNuspecs { ref paths } => {
+                write!(f, "The package contains multiple nuspecs: {}", paths.join(", "))
+            }
+            VerifyProblem::MissingRels => write!(f, "The package doesn't contain '{}'", RELS_PATH),
+            VerifyProblem::MissingContentTypes => {
+                write!(f, "The package doesn't contain '{}'", CONTENT_TYPES_PATH)
+            }
+            VerifyProblem::BadContentTypes => write!(f, "The package's '{}' is invalid", CONTENT_TYPES_PATH),
+            VerifyProblem::UnregisteredContentType { ref path } => {
+                write!(f, "The lib '{}' doesn't have a registered content type", path)
+            }
+        }
+    }
+}
+
+/// Open a `nupkg` and check its structure.
+///
+/// Every problem found is reported, rather than stopping at the first.
+/// An error is only returned if the archive itself can't be read.
+pub fn verify(buf: &Buf) -> Result<VerifyReport, NugetPackError> {
+    let mut archive = ZipArchive::new(Cursor::new(&buf[..]))?;
+
+    let mut names = Vec::new();
+    let mut content_types = None;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().replace("\\", "/");
+
+        if name == CONTENT_TYPES_PATH {
+            let mut xml = Vec::new();
+            entry.read_to_end(&mut xml)?;
+
+            content_types = Some(xml);
+        }
+
+        names.push(name);
+    }
+
+    let mut report = VerifyReport::default();
+
+    let nuspecs: Vec<_> = names
+        .iter()
+        .filter(|name| !name.contains('/') && name.ends_with(".nuspec"))
+        .cloned()
+        .collect();
+
+    match nuspecs.len() {
+        0 => report.problems.push(VerifyProblem::MissingNuspec),
+        1 => (),
+        _ => report.problems.push(VerifyProblem::MultipleNuspecs { paths: nuspecs }),
+    }
+
+    if !names.iter().any(|name| name == RELS_PATH) {
+        report.problems.push(VerifyProblem::MissingRels);
+    }
+
+    let extensions = match content_types {
+        Some(xml) => match registered_extensions(&xml) {
+            Some(extensions) => extensions,
+            None => {
+                report.problems.push(VerifyProblem::BadContentTypes);
+                Vec::new()
+            }
+        },
+        None => {
+            report.problems.push(VerifyProblem::MissingContentTypes);
+            Vec::new()
+        }
+    };
+
+    for name in names.iter().filter(|name| name.starts_with("runtimes/")) {
+        let registered = name.rsplit('/')
+            .next()
+            .and_then(|file| file.rfind('.').map(|i| &file[i + 1..]))
+            .map(|ext| extensions.iter().any(|known| known.eq_ignore_ascii_case(ext)))
+            .unwrap_or(false);
+
+        if !registered {
+            report.problems.push(VerifyProblem::UnregisteredContentType { path: name.clone() });
+        }
+    }
+
+    Ok(report)
+}
+
+/// Get the distinct rids with native libs in a `nupkg`.
+///
+/// Rids are taken from `runtimes/{rid}/native/` entries and returned in sorted order.
+pub fn rids_in_package(buf: &Buf) -> Result<Vec<String>, ZipError> {
+    let mut archive = ZipArchive::new(Cursor::new(&buf[..]))?;
+
+    let mut rids = BTreeSet::new();
+
+    for i in 0..archive.len() {
+        let name = archive.by_index(i)?.name().replace("\\", "/");
+        let parts: Vec<_> = name.split('/').collect();
+
+        match &parts[..] {
+            &["runtimes", rid, "native", lib] if !rid.is_empty() && !lib.is_empty() => {
+                rids.insert(rid.to_owned());
+            }
+            _ => (),
+        }
+    }
+
+    Ok(rids.into_iter().collect())
+}
+
+/// Check whether a `nupkg` has a NuGet package signature.
+///
+/// Only the presence of the signature entry is checked, not whether the signature is valid.
+pub fn is_signed(buf: &Buf) -> Result<bool, ZipError> {
+    let mut archive = ZipArchive::new(Cursor::new(&buf[..]))?;
+
+    for i in 0..archive.len() {
+        if archive.by_index(i)?.name() == SIGNATURE_PATH {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Get the extensions registered in a `[Content_Types].xml`.
+fn registered_extensions(xml: &[u8]) -> Option<Vec<String>> {
+    let mut extensions = Vec::new();
+
+    for event in EventReader::new(xml) {
+        match event {
+            Ok(XmlEvent::StartElement { ref name, ref attributes, .. }) if name.local_name == "Default" => {
+                let extension = attributes
+                    .iter()
+                    .find(|attr| attr.name.local_name == "Extension")
+                    .map(|attr| attr.value.clone());
+
+                if let Some(extension) = extension {
+                    extensions.push(extension);
+                }
+            }
+            Ok(_) => (),
+            Err(_) => return None,
+        }
+    }
+
+    Some(extensions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::Path;
+    use zip::write::{FileOptions, ZipWriter};
+
+    use args::{Arch, CrossTarget, Target};
+    use nuget::{pack, NugetPackArgs};
+    use super::*;
+
+    #[test]
+    fn verify_packed() {
+        let spec = b"<package />".to_vec().into();
+        let args = NugetPackArgs::new("some_pkg", "0.1.0", "Someone", "A description for this package", &spec)
+            .with_libs(vec![
+                (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            ]);
+
+        let nupkg = pack(args).unwrap();
+
+        let report = verify(&nupkg.buf).unwrap();
+
+        assert!(report.is_ok(), "{:?}", report);
+    }
+
+    #[test]
+    fn rids_in_packed() {
+        let spec = b"<package />".to_vec().into();
+        let args = NugetPackArgs::new("some_pkg", "0.1.0", "Someone", "A description for this package", &spec)
+            .with_libs(vec![
+                (Target::Cross(CrossTarget::Windows(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+                (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            ]);
+
+        let nupkg = pack(args).unwrap();
+
+        let rids = rids_in_package(&nupkg.buf).unwrap();
+
+        assert_eq!(vec!["linux-x64".to_owned(), "win-x64".to_owned()], rids);
+    }
+
+    #[test]
+    fn signed_packages() {
+        let unsigned = {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+            writer.start_file("some_pkg.nuspec", FileOptions::default()).unwrap();
+            writer.write_all(b"<package />").unwrap();
+
+            let buf: Buf = writer.finish().unwrap().into_inner().into();
+            buf
+        };
+
+        let signed = {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+            writer.start_file("some_pkg.nuspec", FileOptions::default()).unwrap();
+            writer.write_all(b"<package />").unwrap();
+            writer.start_file(SIGNATURE_PATH, FileOptions::default()).unwrap();
+            writer.write_all(b"signature").unwrap();
+
+            let buf: Buf = writer.finish().unwrap().into_inner().into();
+            buf
+        };
+
+        assert!(!is_signed(&unsigned).unwrap());
+        assert!(is_signed(&signed).unwrap());
+    }
+
+    #[test]
+    fn verify_missing_content_types() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        writer.start_file("some_pkg.nuspec", FileOptions::default()).unwrap();
+        writer.write_all(b"<package />").unwrap();
+        writer.start_file("_rels/.rels", FileOptions::default()).unwrap();
+        writer.write_all(b"<Relationships />").unwrap();
+        writer.start_file("runtimes/linux-x64/native/some_pkg.so", FileOptions::default()).unwrap();
+        writer.write_all(b"lib").unwrap();
+
+        let buf: Buf = writer.finish().unwrap().into_inner().into();
+
+        let report = verify(&buf).unwrap();
+
+        assert_eq!(
+            vec![
+                VerifyProblem::MissingContentTypes,
+                VerifyProblem::UnregisteredContentType {
+                    path: "runtimes/linux-x64/native/some_pkg.so".into(),
+                },
+            ],
+            report.problems
+        );
+    }
+}
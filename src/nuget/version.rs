@@ -0,0 +1,146 @@
+//! NuGet version ordering.
+
+use std::cmp::Ordering;
+
+/// A version split into the parts NuGet compares.
+struct NugetVersion<'a> {
+    release: [u64; 4],
+    pre: Vec<&'a str>,
+}
+
+impl<'a> NugetVersion<'a> {
+    /// Parse a version like `1.0.0`, `1.0.0.1` or `1.0.0-beta.2+build`.
+    fn parse(version: &'a str) -> Option<Self> {
+        // Build metadata doesn't take part in ordering
+        let version = version.splitn(2, '+').next().unwrap_or("");
+
+        let mut parts = version.splitn(2, '-');
+        let release = parts.next().unwrap_or("");
+        let pre = parts.next();
+
+        let release_parts: Vec<_> = release.split('.').collect();
+        if release_parts.len() < 2 || release_parts.len() > 4 {
+            return None;
+        }
+
+        let mut nums = [0; 4];
+        for (num, part) in nums.iter_mut().zip(release_parts) {
+            *num = part.parse().ok()?;
+        }
+
+        let pre = match pre {
+            Some(pre) => {
+                let labels: Vec<_> = pre.split('.').collect();
+
+                if labels.iter().any(|label| label.is_empty()) {
+                    return None;
+                }
+
+                labels
+            }
+            None => Vec::new(),
+        };
+
+        Some(NugetVersion {
+            release: nums,
+            pre: pre,
+        })
+    }
+}
+
+/// Compare two versions the way a NuGet feed orders them.
+///
+/// This differs from semver in a few ways:
+///
+/// - versions may have a fourth `revision` part
+/// - alphanumeric prerelease labels are compared ignoring case
+///
+/// As with semver, a release sorts after its prereleases, numeric labels sort
+/// before alphanumeric ones, and build metadata is ignored.
+/// Versions that can't be parsed are compared as strings, ignoring case.
+pub fn nuget_compare(a: &str, b: &str) -> Ordering {
+    match (NugetVersion::parse(a), NugetVersion::parse(b)) {
+        (Some(a), Some(b)) => a.release
+            .cmp(&b.release)
+            .then_with(|| compare_pre(&a.pre, &b.pre)),
+        _ => compare_ignore_case(a, b),
+    }
+}
+
+/// Compare prerelease labels.
+///
+/// A version without any labels is a release, so sorts after one with labels.
+fn compare_pre(a: &[&str], b: &[&str]) -> Ordering {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Greater,
+        (false, true) => return Ordering::Less,
+        (false, false) => (),
+    }
+
+    for (a, b) in a.iter().zip(b) {
+        let ord = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => compare_ignore_case(a, b),
+        };
+
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+
+    a.len().cmp(&b.len())
+}
+
+fn compare_ignore_case(a: &str, b: &str) -> Ordering {
+    a.chars()
+        .map(|c| c.to_ascii_lowercase())
+        .cmp(b.chars().map(|c| c.to_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use semver::Version;
+
+    use super::*;
+
+    #[test]
+    fn compare_release() {
+        assert_eq!(Ordering::Less, nuget_compare("1.0.0", "1.0.1"));
+        assert_eq!(Ordering::Greater, nuget_compare("1.10.0", "1.9.0"));
+        assert_eq!(Ordering::Equal, nuget_compare("1.0.0", "1.0.0+build"));
+        assert_eq!(Ordering::Less, nuget_compare("1.0.0-beta", "1.0.0"));
+    }
+
+    #[test]
+    fn compare_prerelease() {
+        assert_eq!(Ordering::Less, nuget_compare("1.0.0-alpha", "1.0.0-alpha.1"));
+        assert_eq!(Ordering::Less, nuget_compare("1.0.0-alpha.2", "1.0.0-alpha.10"));
+        assert_eq!(Ordering::Less, nuget_compare("1.0.0-1", "1.0.0-alpha"));
+        assert_eq!(Ordering::Less, nuget_compare("1.0.0-alpha.beta", "1.0.0-beta"));
+    }
+
+    #[test]
+    fn compare_diverges_from_semver_on_case() {
+        let (a, b) = ("1.0.0-alpha", "1.0.0-Beta");
+
+        // Semver compares labels by their ASCII bytes, so `B` sorts before `a`
+        let semver = Version::parse(a).unwrap().cmp(&Version::parse(b).unwrap());
+
+        assert_eq!(Ordering::Greater, semver);
+        assert_eq!(Ordering::Less, nuget_compare(a, b));
+        assert_eq!(Ordering::Equal, nuget_compare("1.0.0-RC.1", "1.0.0-rc.1"));
+    }
+
+    #[test]
+    fn compare_diverges_from_semver_on_revision() {
+        assert!(Version::parse("1.0.0.1").is_err());
+
+        assert_eq!(Ordering::Less, nuget_compare("1.0.0", "1.0.0.1"));
+        assert_eq!(Ordering::Equal, nuget_compare("1.0", "1.0.0.0"));
+        assert_eq!(Ordering::Less, nuget_compare("1.0.0.1-beta", "1.0.0.1"));
+    }
+}
@@ -1,26 +1,47 @@
 use std::error::Error;
+use std::path::Path;
 use clap::ArgMatches;
 
-use {cargo, nuget};
+use {cargo, nuget, warnings};
+use report::BuildReport;
+use args::{CONFIG_ARG, FAIL_ON_WARNINGS_ARG, LIB_MANIFEST_ARG};
 
-pub fn call(args: &ArgMatches) -> Result<(), Box<Error>> {
+pub fn call(args: &ArgMatches) -> Result<BuildReport, Box<dyn Error>> {
     let mut cargo_toml = pass!("reading cargo manifest" => args => cargo::parse_toml);
 
-    let local = pass!("adding local version tag" => &cargo_toml => cargo::local_version_tag);
+    let local = pass!("adding local version tag" => (args, &cargo_toml) => cargo::local_version_tag);
 
     cargo_toml.version = local.version;
 
-    let cargo_libs = pass!("building Rust lib" => (args, &cargo_toml) => |args| {
-        let result = cargo::build_local(args);
+    pass!("validating cargo manifest" => &cargo_toml => cargo::check_valid);
 
-        result.map(|result| vec![result])
-    });
+    // Libs listed in a manifest are used instead of building
+    let cargo_libs = match args.value_of(LIB_MANIFEST_ARG) {
+        Some(path) => pass!("reading lib manifest" => nuget::lib_manifest_path(path) => nuget::read_lib_manifest),
+        None => pass!("building Rust lib" => (args, &cargo_toml) => |args| {
+            let result = cargo::build_local(args);
 
-    let nuspec = pass!("building nuspec" => &cargo_toml => nuget::spec);
+            result.map(|result| vec![result])
+        }),
+    };
 
-    let nupkg = pass!("building nupkg" => (&nuspec, &cargo_libs) => nuget::pack);
+    let pack_config = match args.value_of(CONFIG_ARG) {
+        Some(path) => pass!("reading pack config" => Path::new(path) => nuget::load_pack_config),
+        None => nuget::PackConfig::default(),
+    };
+
+    cargo_toml.nuget.tags.extend(pack_config.tags.iter().cloned());
+
+    let nuspec = pass!("building nuspec" => (args, &cargo_toml) => nuget::spec);
+
+    let build_warnings: Vec<_> = cargo_toml.warnings.iter().chain(&nuspec.warnings).cloned().collect();
+
+    let fail_on_warnings = args.is_present(FAIL_ON_WARNINGS_ARG);
+    warnings::check(&build_warnings, fail_on_warnings)?;
+
+    let nupkg = pass!("building nupkg" => (args, &pack_config, &nuspec, &cargo_libs) => nuget::pack);
 
     pass!("saving nupkg" => (args, &nupkg) => nuget::save_nupkg);
 
-    Ok(())
+    Ok(BuildReport::new(&nupkg, build_warnings))
 }
@@ -0,0 +1,65 @@
+//! A summary of a finished build for downstream tooling.
+
+use nuget::{lock_hash, Nupkg};
+
+/// A summary of a packed `nupkg`.
+///
+/// The report can be serialized as JSON when the `json` feature is enabled.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(Serialize))]
+pub struct BuildReport {
+    pub package_name: String,
+    pub rids: Vec<String>,
+    /// The size of the package in bytes.
+    pub size: u64,
+    /// The base64 SHA-512 digest of the package, like NuGet records in lock files.
+    pub sha512: String,
+    pub warnings: Vec<String>,
+}
+
+impl BuildReport {
+    /// Build a report for a packed `nupkg` and the warnings found while building it.
+    pub fn new(nupkg: &Nupkg, warnings: Vec<String>) -> Self {
+        BuildReport {
+            package_name: nupkg.name.to_string(),
+            rids: nupkg.rids.iter().map(|rid| rid.to_string()).collect(),
+            size: nupkg.buf.len() as u64,
+            sha512: lock_hash(&nupkg.buf),
+            warnings: warnings,
+        }
+    }
+
+    /// Format the report as JSON.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        ::serde_json::to_string(self).expect("a build report is always valid JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use args::{Arch, CrossTarget, Target};
+    use nuget::{pack, NugetPackArgs};
+    use super::*;
+
+    #[test]
+    fn report_packed() {
+        let spec = b"<package />".to_vec().into();
+        let args = NugetPackArgs::new("some_pkg", "0.1.0", "Someone", "A description for this package", &spec)
+            .with_libs(vec![
+                (Target::Cross(CrossTarget::Linux(Arch::x64)), Path::new("tests/native/Cargo.toml")),
+            ]);
+
+        let nupkg = pack(args).unwrap();
+
+        let report = BuildReport::new(&nupkg, vec!["A warning".into()]);
+
+        assert_eq!("some_pkg.0.1.0.nupkg", report.package_name);
+        assert_eq!(vec!["linux-x64".to_owned()], report.rids);
+        assert_eq!(nupkg.buf.len() as u64, report.size);
+        assert_eq!(lock_hash(&nupkg.buf), report.sha512);
+        assert_eq!(vec!["A warning".to_owned()], report.warnings);
+    }
+}
@@ -0,0 +1,56 @@
+//! Report warnings collected while building a package.
+
+/// Log warnings, or fail if there are any and `fail_on_warnings` is set.
+pub fn check<'a, I>(warnings: I, fail_on_warnings: bool) -> Result<(), WarningsError>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let warnings: Vec<_> = warnings.into_iter().cloned().collect();
+
+    if warnings.is_empty() {
+        return Ok(());
+    }
+
+    if fail_on_warnings {
+        return Err(WarningsError::Denied { warnings: warnings });
+    }
+
+    for warning in &warnings {
+        warn!("{}", warning);
+    }
+
+    Ok(())
+}
+
+quick_error!{
+    /// An error encountered because warnings aren't allowed.
+    #[derive(Debug)]
+    pub enum WarningsError {
+        /// There were warnings, but the build fails on warnings.
+        Denied { warnings: Vec<String> } {
+            display("The build has {} warning(s), but warnings aren't allowed:\n{}", warnings.len(), warnings.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fail_on_warnings() {
+        let warnings = vec!["The description is too long".to_owned()];
+
+        assert!(check(&warnings, false).is_ok());
+
+        match check(&warnings, true) {
+            Err(WarningsError::Denied { ref warnings }) if warnings.len() == 1 => (),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn no_warnings() {
+        assert!(check(&Vec::new(), true).is_ok());
+    }
+}